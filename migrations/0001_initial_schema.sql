@@ -0,0 +1,56 @@
+-- Everything is IF NOT EXISTS so databases created before migrations were introduced are picked up as-is
+
+CREATE TABLE IF NOT EXISTS cases (
+    guild_id     BIGINT  NOT NULL,
+    case_id      INTEGER NOT NULL,
+    target_id    BIGINT  NOT NULL,
+    moderator_id BIGINT  NOT NULL,
+    action       TEXT    NOT NULL,
+    reason       TEXT,
+    duration     BIGINT,
+    created_at   BIGINT  NOT NULL,
+    PRIMARY KEY (guild_id, case_id)
+);
+
+CREATE TABLE IF NOT EXISTS warnings (
+    id           BIGSERIAL PRIMARY KEY,
+    guild_id     BIGINT NOT NULL,
+    user_id      BIGINT NOT NULL,
+    moderator_id BIGINT NOT NULL,
+    reason       TEXT   NOT NULL,
+    created_at   BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS warn_thresholds (
+    guild_id   BIGINT  NOT NULL,
+    warn_count INTEGER NOT NULL,
+    action     TEXT    NOT NULL,
+    duration   BIGINT,
+    PRIMARY KEY (guild_id, warn_count)
+);
+
+CREATE TABLE IF NOT EXISTS mutes (
+    guild_id   BIGINT NOT NULL,
+    user_id    BIGINT NOT NULL,
+    role_id    BIGINT NOT NULL,
+    expires_at BIGINT,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS tempbans (
+    guild_id   BIGINT NOT NULL,
+    user_id    BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_config (
+    guild_id       BIGINT PRIMARY KEY,
+    modlog_channel BIGINT
+);
+
+ALTER TABLE guild_config
+    ADD COLUMN IF NOT EXISTS prefix          TEXT,
+    ADD COLUMN IF NOT EXISTS mute_role       BIGINT,
+    ADD COLUMN IF NOT EXISTS default_timeout BIGINT,
+    ADD COLUMN IF NOT EXISTS default_purge   BIGINT;
@@ -0,0 +1,17 @@
+-- Channels locked by a running /lockdown
+CREATE TABLE lockdown_channels (
+    guild_id   BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, channel_id)
+);
+
+-- The exact permission overwrites of each locked channel from before the lockdown
+CREATE TABLE lockdown_overwrites (
+    guild_id    BIGINT NOT NULL,
+    channel_id  BIGINT NOT NULL,
+    target_type TEXT   NOT NULL,
+    target_id   BIGINT NOT NULL,
+    allow       BIGINT NOT NULL,
+    deny        BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, channel_id, target_type, target_id)
+);
@@ -0,0 +1,13 @@
+-- Locks and viewlocks to lift once they expire, with the overwrite bits they replaced -
+-- allow/deny are NULL if the target had no overwrite before the lock
+CREATE TABLE timed_locks (
+    guild_id    BIGINT NOT NULL,
+    channel_id  BIGINT NOT NULL,
+    target_type TEXT   NOT NULL,
+    target_id   BIGINT NOT NULL,
+    perms       BIGINT NOT NULL,
+    allow       BIGINT,
+    deny        BIGINT,
+    expires_at  BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, channel_id, target_type, target_id, perms)
+);
@@ -0,0 +1,27 @@
+-- Point-in-time copies of every channel's permission overwrites in a guild
+CREATE TABLE permission_snapshots (
+    id         BIGSERIAL PRIMARY KEY,
+    guild_id   BIGINT NOT NULL,
+    -- NULL for scheduled snapshots
+    created_by BIGINT,
+    created_at BIGINT NOT NULL
+);
+
+CREATE TABLE snapshot_channels (
+    snapshot_id BIGINT NOT NULL REFERENCES permission_snapshots (id) ON DELETE CASCADE,
+    channel_id  BIGINT NOT NULL,
+    name        TEXT   NOT NULL,
+    PRIMARY KEY (snapshot_id, channel_id)
+);
+
+CREATE TABLE snapshot_overwrites (
+    snapshot_id BIGINT NOT NULL REFERENCES permission_snapshots (id) ON DELETE CASCADE,
+    channel_id  BIGINT NOT NULL,
+    target_type TEXT   NOT NULL,
+    target_id   BIGINT NOT NULL,
+    allow       BIGINT NOT NULL,
+    deny        BIGINT NOT NULL,
+    PRIMARY KEY (snapshot_id, channel_id, target_type, target_id)
+);
+
+ALTER TABLE guild_config ADD COLUMN snapshot_interval BIGINT;
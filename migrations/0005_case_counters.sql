@@ -0,0 +1,8 @@
+-- The last case number handed out in each guild - bumped atomically so concurrent cases never share a number
+CREATE TABLE case_counters (
+    guild_id  BIGINT  PRIMARY KEY,
+    last_case INTEGER NOT NULL
+);
+
+INSERT INTO case_counters (guild_id, last_case)
+SELECT guild_id, MAX(case_id) FROM cases GROUP BY guild_id;
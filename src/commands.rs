@@ -1,2 +1,3 @@
 pub mod moderation;
 pub mod misc;
+pub mod config;
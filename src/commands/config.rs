@@ -0,0 +1,175 @@
+use std::time::Duration;
+use humantime::{format_duration, parse_duration};
+use poise::serenity_prelude as serenity;
+use poise::ChoiceParameter;
+
+use crate::config::{ConfigKey, ConfigValue, DEFAULT_PREFIX, DEFAULT_PURGE, DEFAULT_TIMEOUT, MAX_TIMEOUT};
+use crate::Error;
+use crate::Context;
+
+/// Extract an ID from a mention or a raw ID
+fn parse_id(value: &str) -> Option<u64> {
+    value
+        .trim_start_matches("<#")
+        .trim_start_matches("<@&")
+        .trim_end_matches('>')
+        .parse()
+        .ok()
+}
+
+/// Validate a raw value for the given key, returns an error message for the user if it's invalid
+fn parse_value(ctx: Context<'_>, key: ConfigKey, value: &str) -> Result<ConfigValue, String> {
+    match key {
+        ConfigKey::Prefix => {
+            if value.is_empty() || value.len() > 10 || value.contains(char::is_whitespace) {
+                return Err(String::from("The prefix must be 1-10 characters long and contain no spaces"));
+            }
+            Ok(ConfigValue::Text(value.to_string()))
+        }
+        ConfigKey::ModlogChannel => {
+            let guild = ctx.guild().unwrap();
+            match parse_id(value).map(serenity::ChannelId::new) {
+                Some(channel) if guild.channels.contains_key(&channel) => Ok(ConfigValue::Number(channel.get() as i64)),
+                _ => Err(format!("`{}` is not a channel of this server", value))
+            }
+        }
+        ConfigKey::MuteRole => {
+            let guild = ctx.guild().unwrap();
+            match parse_id(value).map(serenity::RoleId::new) {
+                Some(role) if guild.roles.contains_key(&role) => Ok(ConfigValue::Number(role.get() as i64)),
+                _ => Err(format!("`{}` is not a role of this server", value))
+            }
+        }
+        ConfigKey::DefaultTimeout => match parse_duration(value) {
+            Ok(duration) if duration <= MAX_TIMEOUT => {
+                Ok(ConfigValue::Number(duration.as_secs() as i64))
+            }
+            Ok(_) => Err(String::from("Timeouts can be at most 28 days long")),
+            Err(_) => Err(format!("Invalid duration `{}`", value))
+        },
+        ConfigKey::DefaultPurge => match value.parse::<u8>() {
+            Ok(amount) if (1..=100).contains(&amount) => Ok(ConfigValue::Number(amount as i64)),
+            _ => Err(String::from("The default purge amount must be between 1 and 100"))
+        },
+        ConfigKey::SnapshotInterval => match parse_duration(value) {
+            Ok(duration) if duration >= Duration::from_secs(60 * 60) => Ok(ConfigValue::Number(duration.as_secs() as i64)),
+            Ok(_) => Err(String::from("Permission snapshots can be taken at most once an hour")),
+            Err(_) => Err(format!("Invalid duration `{}`", value))
+        }
+    }
+}
+
+/// Base command for server configuration
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("view", "set", "reset"),
+    guild_only = true
+    )
+]
+pub async fn config(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// View the configuration of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn view(ctx: Context<'_>) -> Result<(), Error> {
+    let config = ctx.data().config.get(&ctx.data().db, ctx.guild_id().unwrap()).await?;
+    let unset = |default: String| format!("Not set (default: {})", default);
+    let embed = serenity::CreateEmbed::new()
+        .title("Server configuration")
+        .field(
+            ConfigKey::Prefix.name(),
+            config.prefix.clone().map(|prefix| format!("`{}`", prefix)).unwrap_or_else(|| unset(format!("`{}`", DEFAULT_PREFIX))),
+            false
+        )
+        .field(
+            ConfigKey::ModlogChannel.name(),
+            config.modlog_channel.map(|channel| format!("<#{}>", channel)).unwrap_or_else(|| unset(String::from("disabled"))),
+            false
+        )
+        .field(
+            ConfigKey::MuteRole.name(),
+            config.mute_role.map(|role| format!("<@&{}>", role)).unwrap_or_else(|| unset(String::from("Muted"))),
+            false
+        )
+        .field(
+            ConfigKey::DefaultTimeout.name(),
+            match config.default_timeout {
+                Some(_) => format_duration(config.default_timeout()).to_string(),
+                None => unset(format_duration(DEFAULT_TIMEOUT).to_string())
+            },
+            false
+        )
+        .field(
+            ConfigKey::DefaultPurge.name(),
+            match config.default_purge {
+                Some(_) => config.default_purge().to_string(),
+                None => unset(DEFAULT_PURGE.to_string())
+            },
+            false
+        )
+        .field(
+            ConfigKey::SnapshotInterval.name(),
+            config
+                .snapshot_interval()
+                .map(|interval| format_duration(interval).to_string())
+                .unwrap_or_else(|| unset(String::from("disabled"))),
+            false
+        );
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+
+/// Change a setting of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Setting to change"] key: ConfigKey,
+    #[description = "New value"] value: String
+) -> Result<(), Error> {
+    let parsed = match parse_value(ctx, key, value.trim()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    ctx.data().config.set(&ctx.data().db, ctx.guild_id().unwrap(), key, parsed).await?;
+    ctx.say(format!("Set `{}` to {}", key.name(), value.trim())).await?;
+    Ok(())
+}
+
+
+/// Reset a setting of this server to its default
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "Setting to reset"] key: ConfigKey
+) -> Result<(), Error> {
+    ctx.data().config.reset(&ctx.data().db, ctx.guild_id().unwrap(), key).await?;
+    ctx.say(format!("Reset `{}` to its default", key.name())).await?;
+    Ok(())
+}
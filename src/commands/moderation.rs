@@ -1,4 +1,18 @@
+use std::time::Duration;
+
+/// Pause between two requests of an action run on many members, channels or threads at once,
+/// so large runs don't hit Discord's rate limits
+pub const PACE: Duration = Duration::from_millis(500);
+
+pub mod case;
 pub mod channel;
+pub mod lockdown;
+pub mod mass;
+pub mod modlog;
+pub mod mute;
+pub mod perms;
 pub mod user;
 pub mod purge;
 pub mod role;
+pub mod thread;
+pub mod voice;
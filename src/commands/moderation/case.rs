@@ -0,0 +1,164 @@
+use std::time::Duration;
+use humantime::format_duration;
+use poise::serenity_prelude as serenity;
+
+use crate::commands::moderation::modlog;
+use crate::Error;
+use crate::Context;
+
+/// A single moderation action as stored in the `cases` table
+#[derive(sqlx::FromRow)]
+pub struct Case {
+    pub case_id: i32,
+    pub target_id: i64,
+    pub moderator_id: i64,
+    pub action: String,
+    pub reason: Option<String>,
+    pub duration: Option<i64>,
+    pub created_at: i64
+}
+
+impl Case {
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "**Case #{}** | {} | <t:{}:f>\nModerator: <@{}>",
+            self.case_id,
+            self.action,
+            self.created_at,
+            self.moderator_id
+        );
+        if let Some(duration) = self.duration {
+            summary.push_str(&format!("\nDuration: {}", format_duration(Duration::from_secs(duration as u64))));
+        }
+        summary.push_str(&format!("\nReason: {}", self.reason.as_deref().unwrap_or("None")));
+        summary
+    }
+
+    fn embed(&self) -> serenity::CreateEmbed {
+        let mut embed = serenity::CreateEmbed::new()
+            .title(format!("Case #{} | {}", self.case_id, self.action))
+            .field("Target", format!("<@{}> ({})", self.target_id, self.target_id), true)
+            .field("Moderator", format!("<@{}>", self.moderator_id), true)
+            .field("Reason", self.reason.as_deref().unwrap_or("None"), false);
+        if let Some(duration) = self.duration {
+            embed = embed.field("Duration", format_duration(Duration::from_secs(duration as u64)).to_string(), true);
+        }
+        embed.field("Time", format!("<t:{}:f>", self.created_at), true)
+    }
+}
+
+/// Record a moderation action against a user in the case log without posting it and return its case number -
+/// for bulk actions that post a single summary instead
+pub async fn insert(
+    ctx: Context<'_>,
+    target: serenity::UserId,
+    action: &str,
+    reason: Option<&str>,
+    duration: Option<Duration>
+) -> Result<i32, Error> {
+    let case_id: i32 = sqlx::query_scalar(
+        "WITH next AS (
+            INSERT INTO case_counters (guild_id, last_case) VALUES ($1, 1)
+            ON CONFLICT (guild_id) DO UPDATE SET last_case = case_counters.last_case + 1
+            RETURNING last_case
+        )
+        INSERT INTO cases (guild_id, case_id, target_id, moderator_id, action, reason, duration, created_at)
+        SELECT $1, last_case, $2, $3, $4, $5, $6, $7 FROM next
+        RETURNING case_id"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .bind(target.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(action)
+    .bind(reason)
+    .bind(duration.map(|duration| duration.as_secs() as i64))
+    .bind(serenity::Timestamp::now().unix_timestamp())
+    .fetch_one(&ctx.data().db)
+    .await?;
+    Ok(case_id)
+}
+
+/// Record a moderation action against a user in the case log, post it to the mod-log and return its case number
+pub async fn record(
+    ctx: Context<'_>,
+    target: serenity::UserId,
+    action: &str,
+    reason: Option<&str>,
+    duration: Option<Duration>
+) -> Result<i32, Error> {
+    let case_id = insert(ctx, target, action, reason, duration).await?;
+    let mut entry = modlog::LogEntry::new(action)
+        .target(format!("<@{}> ({})", target, target))
+        .case_id(case_id);
+    if let Some(reason) = reason {
+        entry = entry.reason(reason);
+    }
+    if let Some(duration) = duration {
+        entry = entry.duration(duration);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(case_id)
+}
+
+
+/// Show a single moderation case
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn case(
+    ctx: Context<'_>,
+    #[description = "Case number"] id: i32
+) -> Result<(), Error> {
+    let case: Option<Case> = sqlx::query_as("SELECT * FROM cases WHERE guild_id = $1 AND case_id = $2")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(id)
+        .fetch_optional(&ctx.data().db)
+        .await?;
+    match case {
+        Some(case) => {
+            ctx.send(poise::CreateReply::default().embed(case.embed())).await?;
+        }
+        None => {
+            ctx.say(format!("Case #{} not found", id)).await?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Page through the moderation history of a user
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn cases(
+    ctx: Context<'_>,
+    #[description = "User whose history to show"] user: serenity::User
+) -> Result<(), Error> {
+    let cases: Vec<Case> = sqlx::query_as("SELECT * FROM cases WHERE guild_id = $1 AND target_id = $2 ORDER BY case_id DESC")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(user.id.get() as i64)
+        .fetch_all(&ctx.data().db)
+        .await?;
+    if cases.is_empty() {
+        ctx.say(format!("No cases found for **{}**", user.name)).await?;
+        return Ok(());
+    }
+    let pages: Vec<String> = cases
+        .chunks(5)
+        .map(|chunk| {
+            let body = chunk.iter().map(Case::summary).collect::<Vec<String>>().join("\n\n");
+            format!("Cases for **{}** ({} total)\n\n{}", user.name, cases.len(), body)
+        })
+        .collect();
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
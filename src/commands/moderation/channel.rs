@@ -1,13 +1,417 @@
-use humantime::parse_duration;
+use std::time::Duration;
+use humantime::{format_duration, parse_duration};
 use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::Mentionable;
+use poise::ChoiceParameter;
 // use poise::CreateReply;
 
 
+use crate::commands::moderation::modlog;
 use crate::Error;
 use crate::Context;
 
-/// Lock a channel - If both user and role mentioned only locks only for role
+/// Permissions denied by `lock`
+pub const LOCK_PERMISSIONS: serenity::Permissions =
+    serenity::Permissions::SEND_MESSAGES.union(serenity::Permissions::SEND_MESSAGES_IN_THREADS);
+
+/// Describe the target of a permission overwrite for the mod-log
+pub fn overwrite_target(guild_id: serenity::GuildId, kind: serenity::PermissionOverwriteType) -> String {
+    match kind {
+        serenity::PermissionOverwriteType::Role(role) if role == guild_id.everyone_role() => String::from("@everyone"),
+        serenity::PermissionOverwriteType::Role(role) => role.mention().to_string(),
+        serenity::PermissionOverwriteType::Member(user) => user.mention().to_string(),
+        _ => String::from("Unknown")
+    }
+}
+
+/// Deny `perms` for `kind` in `channel`, keeping the rest of any existing overwrite intact
+pub async fn deny_overwrite(
+    http: &serenity::Http,
+    channel: &serenity::GuildChannel,
+    kind: serenity::PermissionOverwriteType,
+    perms: serenity::Permissions
+) -> Result<(), Error> {
+    let (allow, deny) = match channel.permission_overwrites.iter().find(|ovr| ovr.kind == kind) {
+        Some(ovr) => (ovr.allow & !perms, ovr.deny | perms),
+        None => (serenity::Permissions::empty(), perms)
+    };
+    channel.create_permission(http, serenity::PermissionOverwrite { allow, deny, kind }).await?;
+    Ok(())
+}
+
+/// Lift a deny of `perms` for `kind` in `channel` - `state` true explicitly allows them, false resets them to default
+/// and removes the overwrite once nothing is left in it
+pub async fn clear_overwrite(
+    http: &serenity::Http,
+    channel: &serenity::GuildChannel,
+    kind: serenity::PermissionOverwriteType,
+    perms: serenity::Permissions,
+    state: bool
+) -> Result<(), Error> {
+    let allow = if state { perms } else { serenity::Permissions::empty() };
+    let existing = channel.permission_overwrites.iter().find(|ovr| ovr.kind == kind);
+    let (allow, deny) = match existing {
+        Some(ovr) => (allow | (ovr.allow & !perms), ovr.deny & !perms),
+        None => (allow, serenity::Permissions::empty())
+    };
+    // An overwrite left with nothing set is removed rather than kept around empty
+    if allow.is_empty() && deny.is_empty() {
+        if existing.is_some() {
+            channel.delete_permission(http, kind).await?;
+        }
+        return Ok(());
+    }
+    channel.create_permission(http, serenity::PermissionOverwrite { allow, deny, kind }).await?;
+    Ok(())
+}
+
+/// A permission overwrite as stored in the database
+#[derive(PartialEq, sqlx::FromRow)]
+pub struct StoredOverwrite {
+    pub target_type: String,
+    pub target_id: i64,
+    pub allow: i64,
+    pub deny: i64
+}
+
+impl StoredOverwrite {
+    /// Convert an overwrite for storage - `None` for target kinds this bot doesn't know
+    pub fn new(overwrite: &serenity::PermissionOverwrite) -> Option<Self> {
+        let (target_type, target_id) = target_key(overwrite.kind)?;
+        Some(Self {
+            target_type: target_type.to_string(),
+            target_id,
+            allow: overwrite.allow.bits() as i64,
+            deny: overwrite.deny.bits() as i64
+        })
+    }
+
+    pub fn overwrite(&self) -> Option<serenity::PermissionOverwrite> {
+        Some(serenity::PermissionOverwrite {
+            allow: serenity::Permissions::from_bits_truncate(self.allow as u64),
+            deny: serenity::Permissions::from_bits_truncate(self.deny as u64),
+            kind: overwrite_kind(&self.target_type, self.target_id)?
+        })
+    }
+}
+
+/// The `target_type` and `target_id` an overwrite target is stored as
+fn target_key(kind: serenity::PermissionOverwriteType) -> Option<(&'static str, i64)> {
+    match kind {
+        serenity::PermissionOverwriteType::Role(role) => Some(("role", role.get() as i64)),
+        serenity::PermissionOverwriteType::Member(user) => Some(("member", user.get() as i64)),
+        _ => None
+    }
+}
+
+/// The overwrite target for a stored `target_type` and `target_id`
+fn overwrite_kind(target_type: &str, target_id: i64) -> Option<serenity::PermissionOverwriteType> {
+    match target_type {
+        "role" => Some(serenity::PermissionOverwriteType::Role(serenity::RoleId::new(target_id as u64))),
+        "member" => Some(serenity::PermissionOverwriteType::Member(serenity::UserId::new(target_id as u64))),
+        _ => None
+    }
+}
+
+/// Replace every permission overwrite of `channel` with `overwrites`
+pub async fn restore_overwrites(
+    http: &serenity::Http,
+    channel: serenity::ChannelId,
+    overwrites: &[StoredOverwrite]
+) -> Result<(), Error> {
+    let overwrites = overwrites.iter().filter_map(StoredOverwrite::overwrite);
+    channel.edit(http, serenity::EditChannel::new().permissions(overwrites)).await?;
+    Ok(())
+}
+
+/// A lock or viewlock to lift once it expires, as stored in the `timed_locks` table
+#[derive(sqlx::FromRow)]
+pub struct TimedLock {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub target_type: String,
+    pub target_id: i64,
+    pub perms: i64,
+    pub allow: Option<i64>,
+    pub deny: Option<i64>
+}
+
+impl TimedLock {
+    /// Put the locked permissions back the way they were before the lock, leaving the rest of the
+    /// overwrite as it is now - removes the overwrite if the lock created it and nothing else was added
+    pub async fn lift(&self, http: &serenity::Http, channel: &serenity::GuildChannel) -> Result<(), serenity::Error> {
+        let Some(kind) = overwrite_kind(&self.target_type, self.target_id) else {
+            return Ok(());
+        };
+        let perms = serenity::Permissions::from_bits_truncate(self.perms as u64);
+        let previous = |bits: Option<i64>| serenity::Permissions::from_bits_truncate(bits.unwrap_or_default() as u64) & perms;
+        let (allow, deny) = match channel.permission_overwrites.iter().find(|ovr| ovr.kind == kind) {
+            Some(ovr) => (ovr.allow & !perms, ovr.deny & !perms),
+            None => (serenity::Permissions::empty(), serenity::Permissions::empty())
+        };
+        let (allow, deny) = (allow | previous(self.allow), deny | previous(self.deny));
+        if allow.is_empty() && deny.is_empty() && self.allow.is_none() {
+            channel.delete_permission(http, kind).await
+        } else {
+            channel.create_permission(http, serenity::PermissionOverwrite { allow, deny, kind }).await
+        }
+    }
+}
+
+/// Remember the overwrite `channel` has for `kind` so a lock of `perms` can be lifted after `duration`
+async fn schedule_unlock(
+    ctx: Context<'_>,
+    channel: &serenity::GuildChannel,
+    kind: serenity::PermissionOverwriteType,
+    perms: serenity::Permissions,
+    duration: Duration
+) -> Result<(), Error> {
+    let Some((target_type, target_id)) = target_key(kind) else {
+        return Ok(());
+    };
+    let previous = channel.permission_overwrites.iter().find(|ovr| ovr.kind == kind);
+    sqlx::query(
+        "INSERT INTO timed_locks (guild_id, channel_id, target_type, target_id, perms, allow, deny, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (guild_id, channel_id, target_type, target_id, perms) DO UPDATE SET expires_at = EXCLUDED.expires_at"
+    )
+    .bind(channel.guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .bind(target_type)
+    .bind(target_id)
+    .bind(perms.bits() as i64)
+    .bind(previous.map(|ovr| ovr.allow.bits() as i64))
+    .bind(previous.map(|ovr| ovr.deny.bits() as i64))
+    .bind(serenity::Timestamp::now().unix_timestamp() + duration.as_secs() as i64)
+    .execute(&ctx.data().db)
+    .await?;
+    Ok(())
+}
+
+/// Drop a scheduled unlock of `perms` for `kind` once the channel is unlocked by hand
+async fn cancel_unlock(
+    ctx: Context<'_>,
+    channel: &serenity::GuildChannel,
+    kind: serenity::PermissionOverwriteType,
+    perms: serenity::Permissions
+) -> Result<(), Error> {
+    let Some((target_type, target_id)) = target_key(kind) else {
+        return Ok(());
+    };
+    sqlx::query(
+        "DELETE FROM timed_locks WHERE guild_id = $1 AND channel_id = $2 AND target_type = $3 AND target_id = $4 AND perms = $5"
+    )
+    .bind(channel.guild_id.get() as i64)
+    .bind(channel.id.get() as i64)
+    .bind(target_type)
+    .bind(target_id)
+    .bind(perms.bits() as i64)
+    .execute(&ctx.data().db)
+    .await?;
+    Ok(())
+}
+
+/// Whether `role` is a role of the guild
+fn is_role(ctx: Context<'_>, role: serenity::RoleId) -> bool {
+    ctx.guild().unwrap().roles.contains_key(&role)
+}
+
+/// Whether `user` is a member of the guild, asking Discord if they're not cached
+async fn is_member(ctx: Context<'_>, user: serenity::UserId) -> bool {
+    if ctx.guild().unwrap().members.contains_key(&user) {
+        return true;
+    }
+    ctx.guild_id().unwrap().member(ctx, user).await.is_ok()
+}
+
+/// Parse role/user mentions or IDs separated by spaces or commas into overwrite targets - defaults to @everyone.
+/// Every target is checked against the guild's roles and members, so a typo doesn't create a stray overwrite
+async fn parse_targets(ctx: Context<'_>, targets: Option<&str>) -> Result<Vec<serenity::PermissionOverwriteType>, String> {
+    let everyone = serenity::PermissionOverwriteType::Role(ctx.guild_id().unwrap().everyone_role());
+    let parse_id = |id: &str| id.parse::<u64>().ok().filter(|id| *id != 0);
+    let mut kinds = Vec::new();
+    for token in targets.unwrap_or_default().split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+        let kind = if token == "@everyone" || token == "everyone" {
+            Some(everyone)
+        } else if let Some(id) = token.strip_prefix("<@&").and_then(|token| token.strip_suffix('>')) {
+            parse_id(id)
+                .map(serenity::RoleId::new)
+                .filter(|role| is_role(ctx, *role))
+                .map(serenity::PermissionOverwriteType::Role)
+        } else if let Some(id) = token.strip_prefix("<@").and_then(|token| token.strip_suffix('>')) {
+            match parse_id(id.trim_start_matches('!')).map(serenity::UserId::new) {
+                Some(user) if is_member(ctx, user).await => Some(serenity::PermissionOverwriteType::Member(user)),
+                _ => None
+            }
+        } else {
+            match parse_id(token) {
+                Some(id) if is_role(ctx, serenity::RoleId::new(id)) => {
+                    Some(serenity::PermissionOverwriteType::Role(serenity::RoleId::new(id)))
+                }
+                Some(id) if is_member(ctx, serenity::UserId::new(id)).await => {
+                    Some(serenity::PermissionOverwriteType::Member(serenity::UserId::new(id)))
+                }
+                _ => None
+            }
+        };
+        match kind {
+            Some(kind) if !kinds.contains(&kind) => kinds.push(kind),
+            Some(_) => {}
+            None => return Err(format!("`{}` is not a role or member of this server", token))
+        }
+    }
+    if kinds.is_empty() {
+        kinds.push(everyone);
+    }
+    Ok(kinds)
+}
+
+/// Reply without pinging any of the roles or users mentioned
+async fn say_quietly(ctx: Context<'_>, content: String) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+    ).await?;
+    Ok(())
+}
+
+/// What a permission is set to in an overwrite
+#[derive(poise::ChoiceParameter, Clone, Copy, PartialEq)]
+pub enum OverwriteState {
+    Allow,
+    Deny,
+    Inherit
+}
+
+/// Suggest permission names containing what has been typed so far
+async fn autocomplete_permission(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.trim().to_uppercase().replace(' ', "_");
+    serenity::Permissions::all()
+        .iter_names()
+        .filter(|(name, _)| name.contains(&partial))
+        .take(25)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Set `perms` to `state` in a channel for every target - a deny can be lifted again after `duration`
+pub async fn set_targets(
+    ctx: Context<'_>,
+    action: &str,
+    channel: Option<serenity::GuildChannel>,
+    targets: Option<String>,
+    perms: serenity::Permissions,
+    state: OverwriteState,
+    duration: Option<String>
+) -> Result<(), Error> {
+    let duration = match duration.as_deref().map(parse_duration) {
+        Some(Ok(duration)) => Some(duration),
+        Some(Err(_)) => {
+            ctx.say(format!("Invalid duration `{}`", duration.unwrap())).await?;
+            return Ok(());
+        }
+        None => None
+    };
+    let kinds = match parse_targets(ctx, targets.as_deref()).await {
+        Ok(kinds) => kinds,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let channel = match channel {
+        Some(channel) => channel,
+        None => ctx.guild_channel().await.unwrap()
+    };
+    let guild_id = ctx.guild_id().unwrap();
+    let mut changed = Vec::new();
+    let mut failed = Vec::new();
+    for kind in kinds {
+        let result = match state {
+            OverwriteState::Deny => deny_overwrite(ctx.http(), &channel, kind, perms).await,
+            OverwriteState::Allow => clear_overwrite(ctx.http(), &channel, kind, perms, true).await,
+            OverwriteState::Inherit => clear_overwrite(ctx.http(), &channel, kind, perms, false).await
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to set an overwrite for {:?} in {}: {}", kind, channel.id, e);
+            failed.push(format!("{} - {}", overwrite_target(guild_id, kind), e));
+            continue;
+        }
+        let scheduled = match duration {
+            Some(duration) if state == OverwriteState::Deny => schedule_unlock(ctx, &channel, kind, perms, duration).await,
+            _ => cancel_unlock(ctx, &channel, kind, perms).await
+        };
+        if let Err(e) = scheduled {
+            tracing::warn!("Failed to update the timed lock of {:?} in {}: {}", kind, channel.id, e);
+            failed.push(format!("{} - set, but the unlock could not be scheduled: {}", overwrite_target(guild_id, kind), e));
+        }
+        changed.push(overwrite_target(guild_id, kind));
+    }
+    let setting = format!("{} set to {}", perms.get_permission_names().join(", "), state.name());
+    let period = duration.map(|duration| format!(" for {}", format_duration(duration))).unwrap_or_default();
+    let mut reply = if changed.is_empty() {
+        format!("{} {} failed", action, channel.mention())
+    } else {
+        format!("{} {}{} - {} for:\n- {}", action, channel.mention(), period, setting, changed.join("\n- "))
+    };
+    if !failed.is_empty() {
+        reply.push_str(&format!("\nFailed for:\n- {}", failed.join("\n- ")));
+    }
+    say_quietly(ctx, reply).await?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let mut entry = modlog::LogEntry::new(action)
+        .target(changed.join(", "))
+        .channel(channel.id)
+        .details(setting);
+    if let Some(duration) = duration {
+        entry = entry.duration(duration);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
+
+/// Base command for channel permission overwrites
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    subcommands("set"),
+    guild_only = true
+    )
+]
+pub async fn overwrite(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Allow, deny or inherit a single permission in a channel for one or more roles and users
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to set the overwrite in"] channel: serenity::GuildChannel,
+    #[description = "Roles/users to set the overwrite for, @everyone for everyone"] targets: String,
+    #[description = "Permission to set, e.g. ADD_REACTIONS"] #[autocomplete = "autocomplete_permission"] permission: String,
+    #[description = "Allow, deny or inherit the permission"] state: OverwriteState
+) -> Result<(), Error> {
+    let Some(perms) = serenity::Permissions::from_name(&permission.trim().to_uppercase().replace(' ', "_")) else {
+        ctx.say(format!("`{}` is not a permission", permission)).await?;
+        return Ok(());
+    };
+    set_targets(ctx, "Overwrite", Some(channel), Some(targets), perms, state, None).await
+}
+
+
+/// Lock a channel for one or more roles and users - defaults to everyone
 #[poise::command(
     slash_command,
     prefix_command,
@@ -18,55 +422,14 @@ use crate::Context;
 pub async fn lock(
     ctx: Context<'_>,
     #[description = "Channel to lock - defaults to current channel"] channel: Option<serenity::GuildChannel>,
-    #[description = "Role to lock channel for - defaults to everyone"] role: Option<serenity::Role>,
-    #[description = "User to lock channel for"] user: Option<serenity::User>
+    #[description = "Roles/users to lock channel for - defaults to everyone"] targets: Option<String>,
+    #[description = "How long to lock for, e.g. 10m - defaults to until unlocked"] duration: Option<String>
 ) -> Result<(), Error> {
-    let channel_ = ctx.guild_channel().await.unwrap();
-    let channel = channel.unwrap_or_else(|| channel_);
-    let user = user.unwrap_or_default();let role = role.unwrap_or_default();
-    let overwrites = channel.permission_overwrites.clone();
-    let ovrwrt: serenity::PermissionOverwriteType;
-    if role != serenity::Role::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Role(role.id);
-        ctx.say(format!("Locking {} for role **{}**", channel.mention(), role.name)).await?;
-    }
-    else if user != serenity::User::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Member(user.id);
-        ctx.say(format!("Locking {} for user **{}**", channel.mention(), user.name)).await?;
-    }
-    else {
-        ovrwrt = serenity::PermissionOverwriteType::Role(ctx.guild_id().unwrap().everyone_role());
-        ctx.say(format!("Locking {} for everyone", channel.mention())).await?;
-    }
-    let mut found = false;
-    for idx in 0..overwrites.len() {
-        if overwrites[idx].kind == ovrwrt {
-            found = true;
-            channel.create_permission(
-                ctx.http(),
-                serenity::PermissionOverwrite {
-                    allow: (overwrites[idx].allow & !serenity::Permissions::SEND_MESSAGES) & !serenity::Permissions::SEND_MESSAGES_IN_THREADS,
-                    deny: overwrites[idx].deny | serenity::Permissions::SEND_MESSAGES | serenity::Permissions::SEND_MESSAGES_IN_THREADS,
-                    kind: overwrites[idx].kind
-                }
-                ).await?;
-        }
-    }
-    if !found {
-        channel.create_permission(
-            ctx.http(),
-            serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::SEND_MESSAGES,
-                kind: ovrwrt
-            }
-            ).await?;
-    }
-    Ok(())
+    set_targets(ctx, "Lock", channel, targets, LOCK_PERMISSIONS, OverwriteState::Deny, duration).await
 }
 
 
-/// Unlock a channel - If both user and role mentioned only unlocks only for role
+/// Unlock a channel for one or more roles and users - defaults to everyone
 #[poise::command(
     slash_command,
     prefix_command,
@@ -76,68 +439,16 @@ pub async fn lock(
 ]
 pub async fn unlock(
     ctx: Context<'_>,
-    #[description = "Channel to lock - defaults to current channel"] channel: Option<serenity::GuildChannel>,
-    #[description = "Role to lock channel for - defaults to everyone"] role: Option<serenity::Role>,
-    #[description = "User to lock channel for"] user: Option<serenity::User>,
-    #[description = "State to set SEND_MESSAGES to, true for true, false for default - defaults to true"] state: Option<bool>
+    #[description = "Channel to unlock - defaults to current channel"] channel: Option<serenity::GuildChannel>,
+    #[description = "Roles/users to unlock channel for - defaults to everyone"] targets: Option<String>,
+    #[description = "True to allow sending messages and in threads, false for default - defaults to false"] state: Option<bool>
 ) -> Result<(), Error> {
-    let channel_ = ctx.guild_channel().await.unwrap();
-    let channel = channel.unwrap_or_else(|| channel_);
-    let user = user.unwrap_or_default();let role = role.unwrap_or_default();
-    let state = state.unwrap_or_default();
-    let overwrites = channel.permission_overwrites.clone();
-    let ovrwrt: serenity::PermissionOverwriteType;
-    let allow: serenity::Permissions;
-    let mut state_str = String::new();
-    if state {
-        allow = serenity::Permissions::SEND_MESSAGES | serenity::Permissions::SEND_MESSAGES_IN_THREADS;
-        state_str.push_str("True");
-    }
-    else {
-        allow = serenity::Permissions::empty();
-        state_str.push_str("Default");
-    }
-    if role != serenity::Role::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Role(role.id);
-        ctx.say(format!("Unlocking {} for role **{}** with state {}", channel.mention(), role.name, state_str)).await?;
-    }
-    else if user != serenity::User::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Member(user.id);
-        ctx.say(format!("Unlocking {} for user **{}** with state {}", channel.mention(), user.name, state_str)).await?;
-    }
-    else {
-        ovrwrt = serenity::PermissionOverwriteType::Role(ctx.guild_id().unwrap().everyone_role());
-        ctx.say(format!("Unlocking {} for everyone with state {}", channel.mention(), state_str)).await?;
-    }
-    let mut found = false;
-    for idx in 0..overwrites.len() {
-        if overwrites[idx].kind == ovrwrt {
-            found = true;
-            channel.create_permission(
-                ctx.http(),
-                serenity::PermissionOverwrite {
-                    allow: allow | ((overwrites[idx].allow & !serenity::Permissions::SEND_MESSAGES) & !serenity::Permissions::SEND_MESSAGES_IN_THREADS),
-                    deny: overwrites[idx].deny & !serenity::Permissions::SEND_MESSAGES & !serenity::Permissions::SEND_MESSAGES_IN_THREADS,
-                    kind: overwrites[idx].kind
-                }
-                ).await?;
-        }
-    }
-    if !found {
-        channel.create_permission(
-            ctx.http(),
-            serenity::PermissionOverwrite {
-                allow,
-                deny: serenity::Permissions::empty(),
-                kind: ovrwrt
-            }
-            ).await?;
-    }
-    Ok(())
+    let state = if state.unwrap_or_default() { OverwriteState::Allow } else { OverwriteState::Inherit };
+    set_targets(ctx, "Unlock", channel, targets, LOCK_PERMISSIONS, state, None).await
 }
 
 
-/// Viewlock a channel - If both user and role mentioned only locks only for role
+/// Viewlock a channel for one or more roles and users - defaults to everyone
 #[poise::command(
     slash_command,
     prefix_command,
@@ -148,55 +459,14 @@ pub async fn unlock(
 pub async fn viewlock(
     ctx: Context<'_>,
     #[description = "Channel to viewlock - defaults to current channel"] channel: Option<serenity::GuildChannel>,
-    #[description = "Role to viewlock channel for - defaults to everyone"] role: Option<serenity::Role>,
-    #[description = "User to viewlock channel for"] user: Option<serenity::User>
+    #[description = "Roles/users to viewlock channel for - defaults to everyone"] targets: Option<String>,
+    #[description = "How long to viewlock for, e.g. 10m - defaults to until unviewlocked"] duration: Option<String>
 ) -> Result<(), Error> {
-    let channel_ = ctx.guild_channel().await.unwrap();
-    let channel = channel.unwrap_or_else(|| channel_);
-    let user = user.unwrap_or_default();let role = role.unwrap_or_default();
-    let overwrites = channel.permission_overwrites.clone();
-    let ovrwrt: serenity::PermissionOverwriteType;
-    if role != serenity::Role::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Role(role.id);
-        ctx.say(format!("Viewlocking {} for role **{}**", channel.mention(), role.name)).await?;
-    }
-    else if user != serenity::User::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Member(user.id);
-        ctx.say(format!("Viewlocking {} for user **{}**", channel.mention(), user.name)).await?;
-    }
-    else {
-        ovrwrt = serenity::PermissionOverwriteType::Role(ctx.guild_id().unwrap().everyone_role());
-        ctx.say(format!("Viewlocking {} for everyone", channel.mention())).await?;
-    }
-    let mut found = false;
-    for idx in 0..overwrites.len() {
-        if overwrites[idx].kind == ovrwrt {
-            found = true;
-            channel.create_permission(
-                ctx.http(),
-                serenity::PermissionOverwrite {
-                    allow: overwrites[idx].allow & !serenity::Permissions::VIEW_CHANNEL,
-                    deny: overwrites[idx].deny | serenity::Permissions::VIEW_CHANNEL,
-                    kind: overwrites[idx].kind
-                }
-                ).await?;
-        }
-    }
-    if !found {
-        channel.create_permission(
-            ctx.http(),
-            serenity::PermissionOverwrite {
-                allow: serenity::Permissions::empty(),
-                deny: serenity::Permissions::VIEW_CHANNEL,
-                kind: ovrwrt
-            }
-            ).await?;
-    }
-    Ok(())
+    set_targets(ctx, "Viewlock", channel, targets, serenity::Permissions::VIEW_CHANNEL, OverwriteState::Deny, duration).await
 }
 
 
-/// Unviewlock a channel - If both user and role mentioned only unlocks only for role
+/// Unviewlock a channel for one or more roles and users - defaults to everyone
 #[poise::command(
     slash_command,
     prefix_command,
@@ -207,63 +477,23 @@ pub async fn viewlock(
 pub async fn unviewlock(
     ctx: Context<'_>,
     #[description = "Channel to unviewlock - defaults to current channel"] channel: Option<serenity::GuildChannel>,
-    #[description = "Role to unviewlock channel for - defaults to everyone"] role: Option<serenity::Role>,
-    #[description = "User to unviewlock channel for"] user: Option<serenity::User>,
+    #[description = "Roles/users to unviewlock channel for - defaults to everyone"] targets: Option<String>,
     #[description = "State to set VIEW_CHANNEL to, true for true, false for default - defaults to true"] state: Option<bool>
 ) -> Result<(), Error> {
-    let channel_ = ctx.guild_channel().await.unwrap();
-    let channel = channel.unwrap_or_else(|| channel_);
-    let user = user.unwrap_or_default();let role = role.unwrap_or_default();
-    let state = state.unwrap_or_else(|| true);
-    let overwrites = channel.permission_overwrites.clone();
-    let ovrwrt: serenity::PermissionOverwriteType;
-    let allow: serenity::Permissions;
-    let mut state_str = String::new();
-    if state {
-        allow = serenity::Permissions::VIEW_CHANNEL;
-        state_str.push_str("True");
-    }
-    else {
-        allow = serenity::Permissions::empty();
-        state_str.push_str("Default")
-    }
-    if role != serenity::Role::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Role(role.id);
-        ctx.say(format!("Unviewlocking {} for role **{}** with state {}", channel.mention(), role.name, state_str)).await?;
-    }
-    else if user != serenity::User::default() {
-        ovrwrt = serenity::PermissionOverwriteType::Member(user.id);
-        ctx.say(format!("Unviewlocking {} for user **{}** with state {}", channel.mention(), user.name, state_str)).await?;
-    }
-    else {
-        ovrwrt = serenity::PermissionOverwriteType::Role(ctx.guild_id().unwrap().everyone_role());
-        ctx.say(format!("Unviewlocking {} for everyone with state {}", channel.mention(), state_str)).await?;
-    }
-    let mut found = false;
-    for idx in 0..overwrites.len() {
-        if overwrites[idx].kind == ovrwrt {
-            found = true;
-            channel.create_permission(
-                ctx.http(),
-                serenity::PermissionOverwrite {
-                    allow: allow | (overwrites[idx].allow & !serenity::Permissions::VIEW_CHANNEL),
-                    deny: overwrites[idx].deny & !serenity::Permissions::VIEW_CHANNEL,
-                    kind: overwrites[idx].kind
-                }
-                ).await?;
-        }
-    }
-    if !found {
-        channel.create_permission(
-            ctx.http(),
-            serenity::PermissionOverwrite {
-                allow,
-                deny: serenity::Permissions::empty(),
-                kind: ovrwrt
-            }
-            ).await?;
+    let state = if state.unwrap_or(true) { OverwriteState::Allow } else { OverwriteState::Inherit };
+    set_targets(ctx, "Unviewlock", channel, targets, serenity::Permissions::VIEW_CHANNEL, state, None).await
+}
+
+
+/// Parse a slowmode like `30s` or `2h` into seconds, capped at Discord's maximum of 6 hours
+pub fn parse_slowmode(time: &str) -> Result<u16, String> {
+    let Ok(duration) = parse_duration(time) else {
+        return Err(format!("Invalid duration `{}`", time));
+    };
+    if duration.as_secs() > 21600 {
+        return Err(String::from("Too high a slowmode entered, a maximum of 6 hours is allowed"));
     }
-    Ok(())
+    Ok(duration.as_secs() as u16)
 }
 
 
@@ -282,14 +512,22 @@ pub async fn slowmode(
 ) -> Result<(), Error> {
     let channel_ = ctx.guild_channel().await.unwrap();
     let mut channel = channel.unwrap_or_else(|| channel_);
-    let time_ = parse_duration(&time).unwrap().as_secs();
-    if time_ > 21600 {
-        ctx.say("Too high a slowmode entered, a maximum of 6 hours is allowed").await?;
-        return Ok(());
-    }
+    let time_ = match parse_slowmode(&time) {
+        Ok(time_) => time_,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
     channel.edit(
         &ctx.http(),
-        serenity::EditChannel::new().rate_limit_per_user(time_ as u16)
+        serenity::EditChannel::new().rate_limit_per_user(time_)
+    ).await?;
+    modlog::post(
+        ctx,
+        modlog::LogEntry::new("Slowmode")
+            .channel(channel.id)
+            .duration(std::time::Duration::from_secs(time_ as u64))
     ).await?;
     ctx.say(format!("Successfully set slowmode in {} to {}", channel.mention(), time)).await?;
     Ok(())
@@ -0,0 +1,191 @@
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+use sqlx::PgPool;
+
+use crate::commands::moderation::channel::{deny_overwrite, restore_overwrites, StoredOverwrite, LOCK_PERMISSIONS};
+use crate::commands::moderation::modlog;
+use crate::Error;
+use crate::Context;
+
+/// Save the current overwrites of `channel` so the lockdown can be lifted exactly
+async fn save_overwrites(db: &PgPool, guild_id: serenity::GuildId, channel: &serenity::GuildChannel) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+    sqlx::query("INSERT INTO lockdown_channels (guild_id, channel_id) VALUES ($1, $2)")
+        .bind(guild_id.get() as i64)
+        .bind(channel.id.get() as i64)
+        .execute(&mut *tx)
+        .await?;
+    for overwrite in channel.permission_overwrites.iter().filter_map(StoredOverwrite::new) {
+        sqlx::query(
+            "INSERT INTO lockdown_overwrites (guild_id, channel_id, target_type, target_id, allow, deny)
+            VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(guild_id.get() as i64)
+        .bind(channel.id.get() as i64)
+        .bind(overwrite.target_type)
+        .bind(overwrite.target_id)
+        .bind(overwrite.allow)
+        .bind(overwrite.deny)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Drop the saved overwrites of a channel once it's no longer part of the lockdown
+async fn forget_channel(db: &PgPool, guild_id: serenity::GuildId, channel: i64) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+    for table in ["lockdown_channels", "lockdown_overwrites"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE guild_id = $1 AND channel_id = $2", table))
+            .bind(guild_id.get() as i64)
+            .bind(channel)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Base command for server-wide lockdowns
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    subcommands("start", "end"),
+    guild_only = true
+    )
+]
+pub async fn lockdown(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Lock every text channel of the server or of a category, saving their permissions to restore later
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn start(
+    ctx: Context<'_>,
+    #[description = "Category to lock - defaults to the whole server"] category: Option<serenity::GuildChannel>,
+    #[description = "Reason for the lockdown"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    if let Some(category) = &category {
+        if category.kind != serenity::ChannelType::Category {
+            ctx.say(format!("{} is not a category", category.mention())).await?;
+            return Ok(());
+        }
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let db = &ctx.data().db;
+    let locked: Vec<i64> = sqlx::query_scalar("SELECT channel_id FROM lockdown_channels WHERE guild_id = $1")
+        .bind(guild_id.get() as i64)
+        .fetch_all(db)
+        .await?;
+    let channels: Vec<serenity::GuildChannel> = ctx
+        .guild()
+        .unwrap()
+        .channels
+        .values()
+        .filter(|channel| matches!(channel.kind, serenity::ChannelType::Text | serenity::ChannelType::News))
+        .filter(|channel| category.as_ref().is_none_or(|category| channel.parent_id == Some(category.id)))
+        .filter(|channel| !locked.contains(&(channel.id.get() as i64)))
+        .cloned()
+        .collect();
+    if channels.is_empty() {
+        ctx.say("No channels to lock").await?;
+        return Ok(());
+    }
+
+    let reply = ctx.say(format!("Locking {} channels", channels.len())).await?;
+    let everyone = serenity::PermissionOverwriteType::Role(guild_id.everyone_role());
+    let mut failed = 0;
+    for channel in &channels {
+        save_overwrites(db, guild_id, channel).await?;
+        if let Err(e) = deny_overwrite(ctx.http(), channel, everyone, LOCK_PERMISSIONS).await {
+            tracing::warn!("Failed to lock {} for a lockdown: {}", channel.id, e);
+            forget_channel(db, guild_id, channel.id.get() as i64).await?;
+            failed += 1;
+        }
+    }
+    let mut summary = format!("Locked {} channels", channels.len() - failed);
+    if failed != 0 {
+        summary.push_str(&format!(", failed to lock {}", failed));
+    }
+    reply.edit(ctx, poise::CreateReply::default().content(&summary)).await?;
+
+    let mut entry = modlog::LogEntry::new("Lockdown Start")
+        .target(category.map_or(String::from("Whole server"), |category| category.mention().to_string()))
+        .details(summary);
+    if let Some(reason) = reason {
+        entry = entry.reason(reason);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
+
+
+/// End the lockdown, restoring every locked channel's permissions exactly as they were
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn end(
+    ctx: Context<'_>,
+    #[description = "Reason for ending the lockdown"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let db = &ctx.data().db;
+    let locked: Vec<i64> = sqlx::query_scalar("SELECT channel_id FROM lockdown_channels WHERE guild_id = $1")
+        .bind(guild_id.get() as i64)
+        .fetch_all(db)
+        .await?;
+    if locked.is_empty() {
+        ctx.say("There is no lockdown to end").await?;
+        return Ok(());
+    }
+
+    let reply = ctx.say(format!("Unlocking {} channels", locked.len())).await?;
+    let mut restored = 0;
+    let mut failed = 0;
+    for channel_id in locked {
+        let channel = serenity::ChannelId::new(channel_id as u64);
+        let exists = ctx.guild().unwrap().channels.contains_key(&channel);
+        if exists {
+            let overwrites: Vec<StoredOverwrite> = sqlx::query_as(
+                "SELECT target_type, target_id, allow, deny FROM lockdown_overwrites WHERE guild_id = $1 AND channel_id = $2"
+            )
+            .bind(guild_id.get() as i64)
+            .bind(channel_id)
+            .fetch_all(db)
+            .await?;
+            if let Err(e) = restore_overwrites(ctx.http(), channel, &overwrites).await {
+                tracing::warn!("Failed to restore {} after a lockdown: {}", channel, e);
+                failed += 1;
+                continue;
+            }
+            restored += 1;
+        }
+        forget_channel(db, guild_id, channel_id).await?;
+    }
+    let mut summary = format!("Restored {} channels", restored);
+    if failed != 0 {
+        summary.push_str(&format!(", failed to restore {} - run this again to retry", failed));
+    }
+    reply.edit(ctx, poise::CreateReply::default().content(&summary)).await?;
+
+    let mut entry = modlog::LogEntry::new("Lockdown End").details(summary);
+    if let Some(reason) = reason {
+        entry = entry.reason(reason);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
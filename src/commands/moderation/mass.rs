@@ -0,0 +1,295 @@
+use std::time::Duration;
+use humantime::parse_duration;
+use poise::serenity_prelude as serenity;
+
+use crate::commands::moderation::case;
+use crate::commands::moderation::modlog;
+use crate::commands::moderation::user::forget_tempban;
+use crate::commands::moderation::PACE;
+use crate::Error;
+use crate::Context;
+
+#[derive(Clone, Copy)]
+enum MassAction {
+    Ban(u8),
+    Kick
+}
+
+impl MassAction {
+    fn verb(&self) -> &'static str {
+        match self {
+            MassAction::Ban(_) => "ban",
+            MassAction::Kick => "kick"
+        }
+    }
+
+    /// Ban or kick one user and record the case without posting it - only the Discord request decides
+    /// whether the user counts as failed, bookkeeping errors are just logged
+    async fn run(&self, ctx: Context<'_>, user: serenity::UserId, reason: &str) -> Result<(), serenity::Error> {
+        let guild_id = ctx.guild_id().unwrap();
+        let action = match self {
+            MassAction::Ban(delete_days) => {
+                ctx.http().ban_user(guild_id, user, *delete_days, Some(reason)).await?;
+                if let Err(e) = forget_tempban(ctx, user).await {
+                    tracing::warn!("Failed to drop the tempban of {} after a mass ban: {}", user, e);
+                }
+                "Ban"
+            }
+            MassAction::Kick => {
+                ctx.http().kick_member(guild_id, user, Some(reason)).await?;
+                "Kick"
+            }
+        };
+        if let Err(e) = case::insert(ctx, user, action, Some(reason), None).await {
+            tracing::warn!("Failed to record the mass {} case of {}: {}", self.verb(), user, e);
+        }
+        Ok(())
+    }
+}
+
+/// Members who joined after `joined_after`, and optionally whose accounts were created after `created_after`,
+/// paging through the member list over HTTP since the cache misses members in large guilds
+async fn recent_members(
+    ctx: Context<'_>,
+    joined_after: i64,
+    created_after: Option<i64>
+) -> Result<Vec<serenity::UserId>, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let mut targets = Vec::new();
+    let mut after = None;
+    loop {
+        let page = guild_id.members(ctx.http(), Some(1000), after).await?;
+        targets.extend(
+            page.iter()
+                .filter(|member| member.joined_at.is_some_and(|joined| joined.unix_timestamp() >= joined_after))
+                .filter(|member| created_after.is_none_or(|created| member.user.id.created_at().unix_timestamp() >= created))
+                .map(|member| member.user.id)
+        );
+        match page.last() {
+            Some(last) if page.len() == 1000 => after = Some(last.user.id),
+            _ => break
+        }
+    }
+    Ok(targets)
+}
+
+/// Collect the users targeted by a mass action from an ID list, an attached file or a join window
+async fn collect_targets(
+    ctx: Context<'_>,
+    ids: Option<String>,
+    file: Option<serenity::Attachment>,
+    joined_within: Option<Duration>,
+    account_age: Option<Duration>
+) -> Result<Vec<serenity::UserId>, Error> {
+    let id_regex = regex::Regex::new(r"\d{17,20}").unwrap();
+    let mut text = ids.unwrap_or_default();
+    if let Some(file) = file {
+        text.push(' ');
+        text.push_str(&String::from_utf8_lossy(&file.download().await?));
+    }
+    let mut targets: Vec<serenity::UserId> = id_regex
+        .find_iter(&text)
+        .filter_map(|id| id.as_str().parse::<u64>().ok())
+        .map(serenity::UserId::new)
+        .collect();
+    if let Some(joined_within) = joined_within {
+        let now = serenity::Timestamp::now().unix_timestamp();
+        let joined_after = now - joined_within.as_secs() as i64;
+        let created_after = account_age.map(|age| now - age.as_secs() as i64);
+        targets.extend(recent_members(ctx, joined_after, created_after).await?);
+    }
+    targets.sort();
+    targets.dedup();
+    targets.retain(|id| *id != ctx.author().id && *id != ctx.framework().bot_id);
+    Ok(targets)
+}
+
+/// Ask the invoking moderator to confirm the action, returns the prompt message if confirmed
+async fn confirm(ctx: Context<'_>, action: MassAction, targets: &[serenity::UserId]) -> Result<Option<serenity::Message>, Error> {
+    let mut preview = targets
+        .iter()
+        .take(20)
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    if targets.len() > 20 {
+        preview.push_str(&format!("\n... and {} more", targets.len() - 20));
+    }
+    let embed = serenity::CreateEmbed::new()
+        .description(format!("Confirm {} of **_{}_** users?\n```\n{}\n```", action.verb(), targets.len(), preview));
+    let actionrow: serenity::CreateActionRow = serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new("Yes").emoji('✅'),
+            serenity::CreateButton::new("No").emoji('❌')
+        ]
+    );
+    let mut msg = ctx.send(
+        poise::CreateReply::default()
+        .embed(embed)
+        .components(vec![actionrow])
+    ).await?
+    .into_message().await?;
+    let interaction = msg
+    .await_component_interaction(ctx)
+    .timeout(Duration::from_secs(60 * 5))
+    .author_id(ctx.author().id)
+    .await;
+    let Some(interaction) = interaction else {
+        msg.edit(
+            ctx.http(),
+            serenity::EditMessage::default()
+            .embed(serenity::CreateEmbed::default().description("Action timed out"))
+            .components(vec![])
+        ).await?;
+        return Ok(None);
+    };
+    interaction.defer(&ctx.http()).await?;
+    if &*interaction.data.custom_id != "Yes" {
+        msg.edit(
+            ctx.http(),
+            serenity::EditMessage::default()
+            .suppress_embeds(true)
+            .content("Action cancelled by user")
+            .components(vec![])
+        ).await?;
+        return Ok(None);
+    }
+    Ok(Some(msg))
+}
+
+async fn mass_action(
+    ctx: Context<'_>,
+    action: MassAction,
+    ids: Option<String>,
+    file: Option<serenity::Attachment>,
+    joined_within: Option<String>,
+    account_age: Option<String>,
+    reason: Option<String>
+) -> Result<(), Error> {
+    let mut durations = Vec::new();
+    for time in [&joined_within, &account_age] {
+        match time.as_deref().map(parse_duration) {
+            Some(Ok(duration)) => durations.push(Some(duration)),
+            Some(Err(_)) => {
+                let time = time.as_deref().unwrap();
+                // Prefix invocations with an unquoted ID list spill the second ID into `joined_within`
+                let hint = if time.chars().all(|c| c.is_ascii_digit()) {
+                    format!(" - quote the list of IDs when using the prefix command, e.g. `{} \"111 222\"`", ctx.command().name)
+                } else {
+                    String::new()
+                };
+                ctx.say(format!("Invalid duration `{}`{}", time, hint)).await?;
+                return Ok(());
+            }
+            None => durations.push(None)
+        }
+    }
+    if durations[0].is_none() && durations[1].is_some() {
+        ctx.say("`account_age` can only be used together with `joined_within`").await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let targets = collect_targets(ctx, ids, file, durations[0], durations[1]).await?;
+    if targets.is_empty() {
+        ctx.say("No users to act on").await?;
+        return Ok(());
+    }
+    let Some(mut msg) = confirm(ctx, action, &targets).await? else {
+        return Ok(());
+    };
+    let reason = reason.unwrap_or_else(|| format!("Mass {} requested by {}", action.verb(), ctx.author().name));
+    msg.edit(
+        ctx.http(),
+        serenity::EditMessage::default()
+        .content(format!("Running mass {} on {} users", action.verb(), targets.len()))
+        .suppress_embeds(true)
+        .components(vec![])
+    ).await?;
+    let mut failed: Vec<serenity::UserId> = Vec::new();
+    for (idx, target) in targets.iter().enumerate() {
+        if let Err(e) = action.run(ctx, *target, &reason).await {
+            tracing::warn!("Mass {} of {} failed: {}", action.verb(), target, e);
+            failed.push(*target);
+        }
+        if (idx + 1) % 10 == 0 {
+            let progress = format!("Running mass {} - {}/{} done", action.verb(), idx + 1, targets.len());
+            if let Err(e) = msg.edit(ctx.http(), serenity::EditMessage::default().content(progress)).await {
+                tracing::warn!("Failed to update the progress of a mass {}: {}", action.verb(), e);
+            }
+        }
+        tokio::time::sleep(PACE).await;
+    }
+    let summary = format!(
+        "Mass {} finished - {} succeeded, {} failed",
+        action.verb(),
+        targets.len() - failed.len(),
+        failed.len()
+    );
+    modlog::post(
+        ctx,
+        modlog::LogEntry::new(format!("Mass {}", if let MassAction::Ban(_) = action { "Ban" } else { "Kick" }))
+            .target(format!("{} users", targets.len()))
+            .reason(reason.as_str())
+            .details(summary.clone())
+    ).await?;
+    if let Err(e) = msg.edit(ctx.http(), serenity::EditMessage::default().content(summary)).await {
+        tracing::warn!("Failed to report the result of a mass {}: {}", action.verb(), e);
+    }
+    if !failed.is_empty() {
+        let failed = failed.iter().map(|id| id.to_string()).collect::<Vec<String>>().join("\n");
+        ctx.send(
+            poise::CreateReply::default()
+            .content(format!("Failed to {} the following users:", action.verb()))
+            .attachment(serenity::CreateAttachment::bytes(failed, "failed.txt"))
+        ).await?;
+    }
+    Ok(())
+}
+
+
+/// Ban many users at once by ID or by join window
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "BAN_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn massban(
+    ctx: Context<'_>,
+    #[description = "Space separated user IDs - quote the list when using the prefix command"] ids: Option<String>,
+    #[description = "Text file of user IDs"] file: Option<serenity::Attachment>,
+    #[description = "Target everyone who joined within this long"] joined_within: Option<String>,
+    #[description = "Only target accounts younger than this - requires joined_within"] account_age: Option<String>,
+    #[description = "Reason"] reason: Option<String>,
+    #[description = "Days of message history to delete (0-7) - defaults to 0"]
+    #[min = 0]
+    #[max = 7]
+    delete_days: Option<u8>
+) -> Result<(), Error> {
+    let delete_days = delete_days.unwrap_or_default();
+    if delete_days > 7 {
+        ctx.say("Messages can only be deleted from up to 7 days back").await?;
+        return Ok(());
+    }
+    mass_action(ctx, MassAction::Ban(delete_days), ids, file, joined_within, account_age, reason).await
+}
+
+
+/// Kick many users at once by ID or by join window
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "KICK_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn masskick(
+    ctx: Context<'_>,
+    #[description = "Space separated user IDs - quote the list when using the prefix command"] ids: Option<String>,
+    #[description = "Text file of user IDs"] file: Option<serenity::Attachment>,
+    #[description = "Target everyone who joined within this long"] joined_within: Option<String>,
+    #[description = "Only target accounts younger than this - requires joined_within"] account_age: Option<String>,
+    #[description = "Reason"] reason: Option<String>
+) -> Result<(), Error> {
+    mass_action(ctx, MassAction::Kick, ids, file, joined_within, account_age, reason).await
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+use humantime::format_duration;
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+
+use crate::Error;
+use crate::Context;
+
+/// A moderation action to be posted to the guild's mod-log channel
+pub struct LogEntry {
+    action: String,
+    target: Option<String>,
+    reason: Option<String>,
+    duration: Option<Duration>,
+    channel: Option<serenity::ChannelId>,
+    case_id: Option<i32>,
+    details: Option<String>
+}
+
+impl LogEntry {
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            action: action.into(),
+            target: None,
+            reason: None,
+            duration: None,
+            channel: None,
+            case_id: None,
+            details: None
+        }
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn channel(mut self, channel: serenity::ChannelId) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn case_id(mut self, case_id: i32) -> Self {
+        self.case_id = Some(case_id);
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    fn embed(self, moderator: &serenity::User) -> serenity::CreateEmbed {
+        let title = match self.case_id {
+            Some(case_id) => format!("Case #{} | {}", case_id, self.action),
+            None => self.action
+        };
+        let mut embed = serenity::CreateEmbed::new()
+            .title(title)
+            .field("Moderator", format!("{} ({})", moderator.mention(), moderator.name), true)
+            .timestamp(serenity::Timestamp::now());
+        if let Some(target) = self.target {
+            embed = embed.field("Target", target, true);
+        }
+        if let Some(channel) = self.channel {
+            embed = embed.field("Channel", channel.mention().to_string(), true);
+        }
+        if let Some(duration) = self.duration {
+            embed = embed.field("Duration", format_duration(duration).to_string(), true);
+        }
+        if let Some(reason) = self.reason {
+            embed = embed.field("Reason", reason, false);
+        }
+        if let Some(details) = self.details {
+            embed = embed.field("Details", details, false);
+        }
+        embed
+    }
+}
+
+/// Get the mod-log channel of the current guild, if one is set
+pub async fn channel(ctx: Context<'_>) -> Result<Option<serenity::ChannelId>, Error> {
+    let config = ctx.data().config.get(&ctx.data().db, ctx.guild_id().unwrap()).await?;
+    Ok(config.modlog_channel())
+}
+
+/// Post an entry to the mod-log channel - does nothing if the guild has none set
+pub async fn post(ctx: Context<'_>, entry: LogEntry) -> Result<(), Error> {
+    let Some(channel) = channel(ctx).await? else {
+        return Ok(());
+    };
+    let message = serenity::CreateMessage::new().embed(entry.embed(ctx.author()));
+    if let Err(e) = channel.send_message(ctx.http(), message).await {
+        tracing::warn!("Failed to post to mod-log channel {}: {}", channel, e);
+    }
+    Ok(())
+}
+
+/// Upload files to the mod-log channel, one message each with `content` on the first - does nothing if the
+/// guild has none set. Unlike `post`, a failed upload is returned so callers keeping evidence can hold off
+/// on deleting it
+pub async fn upload(ctx: Context<'_>, content: impl Into<String>, files: Vec<serenity::CreateAttachment>) -> Result<(), Error> {
+    let Some(channel) = channel(ctx).await? else {
+        return Ok(());
+    };
+    let mut content = Some(content.into());
+    for file in files {
+        let mut message = serenity::CreateMessage::new()
+            .add_file(file)
+            .allowed_mentions(serenity::CreateAllowedMentions::new());
+        if let Some(content) = content.take() {
+            message = message.content(content);
+        }
+        channel.send_message(ctx.http(), message).await?;
+    }
+    Ok(())
+}
+
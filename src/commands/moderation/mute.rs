@@ -1 +1,172 @@
+use humantime::{format_duration, parse_duration};
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
 
+use crate::commands::moderation::case;
+use crate::commands::moderation::channel::deny_overwrite;
+use crate::commands::moderation::PACE;
+use crate::config::{ConfigKey, ConfigValue};
+use crate::Error;
+use crate::Context;
+
+const MUTE_ROLE_NAME: &str = "Muted";
+
+fn mute_permissions() -> serenity::Permissions {
+    serenity::Permissions::SEND_MESSAGES
+        | serenity::Permissions::ADD_REACTIONS
+        | serenity::Permissions::SPEAK
+        | serenity::Permissions::SEND_MESSAGES_IN_THREADS
+}
+
+/// Get the mute role of the guild, creating it if it doesn't exist, and deny it in every channel that has no
+/// overwrite for it yet - channels the bot can't edit are skipped, so one private channel doesn't block muting
+async fn mute_role(ctx: Context<'_>) -> Result<serenity::RoleId, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let configured = ctx.data().config.get(&ctx.data().db, guild_id).await?.mute_role();
+    let (role, channels) = {
+        let guild = ctx.guild().unwrap();
+        let role = configured
+            .filter(|role| guild.roles.contains_key(role))
+            .or_else(|| {
+                guild
+                    .roles
+                    .values()
+                    .find(|role| role.name == MUTE_ROLE_NAME)
+                    .map(|role| role.id)
+            });
+        let channels: Vec<serenity::GuildChannel> = guild.channels.values().cloned().collect();
+        (role, channels)
+    };
+    let (role, created) = match role {
+        Some(role) => (role, false),
+        None => {
+            let role = guild_id
+                .create_role(
+                    ctx.http(),
+                    serenity::EditRole::new()
+                        .name(MUTE_ROLE_NAME)
+                        .permissions(serenity::Permissions::empty())
+                )
+                .await?
+                .id;
+            ctx.data()
+                .config
+                .set(&ctx.data().db, guild_id, ConfigKey::MuteRole, ConfigValue::Number(role.get() as i64))
+                .await?;
+            (role, true)
+        }
+    };
+    let kind = serenity::PermissionOverwriteType::Role(role);
+    let missing: Vec<&serenity::GuildChannel> = channels
+        .iter()
+        .filter(|channel| created || !channel.permission_overwrites.iter().any(|ovr| ovr.kind == kind))
+        .collect();
+    if !missing.is_empty() {
+        ctx.defer().await?;
+    }
+    for (idx, channel) in missing.into_iter().enumerate() {
+        if idx != 0 {
+            tokio::time::sleep(PACE).await;
+        }
+        if let Err(e) = deny_overwrite(ctx.http(), channel, kind, mute_permissions()).await {
+            tracing::warn!("Failed to deny the mute role in {}: {}", channel.id, e);
+        }
+    }
+    Ok(role)
+}
+
+/// Remove the mute role from a member and drop their mute entry
+pub async fn lift_mute(
+    http: &serenity::Http,
+    db: &sqlx::PgPool,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    role_id: serenity::RoleId
+) -> Result<(), Error> {
+    http.remove_member_role(guild_id, user_id, role_id, Some("Mute lifted")).await?;
+    sqlx::query("DELETE FROM mutes WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+
+/// Mute a member with the server's mute role
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn mute(
+    ctx: Context<'_>,
+    #[description = "User to mute"] user: serenity::User,
+    #[description = "Time to mute the user for - defaults to indefinitely"] time: Option<String>,
+    #[description = "Reason"] reason: Option<String>
+) -> Result<(), Error> {
+    let duration = match time.as_deref().map(parse_duration) {
+        Some(Ok(duration)) => Some(duration),
+        Some(Err(_)) => {
+            ctx.say(format!("Invalid duration `{}`", time.unwrap())).await?;
+            return Ok(());
+        }
+        None => None
+    };
+    let reason = reason.unwrap_or_else(|| format!("Requested by {}", ctx.author().name));
+    let guild_id = ctx.guild_id().unwrap();
+    let role = mute_role(ctx).await?;
+    ctx.http().add_member_role(guild_id, user.id, role, Some(&reason)).await?;
+    let expires_at = duration.map(|duration| serenity::Timestamp::now().unix_timestamp() + duration.as_secs() as i64);
+    sqlx::query(
+        "INSERT INTO mutes (guild_id, user_id, role_id, expires_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, user_id) DO UPDATE SET role_id = EXCLUDED.role_id, expires_at = EXCLUDED.expires_at"
+    )
+    .bind(guild_id.get() as i64)
+    .bind(user.id.get() as i64)
+    .bind(role.get() as i64)
+    .bind(expires_at)
+    .execute(&ctx.data().db)
+    .await?;
+    case::record(ctx, user.id, "Mute", Some(&reason), duration).await?;
+    match duration {
+        Some(duration) => {
+            ctx.say(format!("Muted {} for {}", user.mention(), format_duration(duration))).await?;
+        }
+        None => {
+            ctx.say(format!("Muted {}", user.mention())).await?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Unmute a member
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn unmute(
+    ctx: Context<'_>,
+    #[description = "User to unmute"] user: serenity::User
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let role: Option<i64> = sqlx::query_scalar("SELECT role_id FROM mutes WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id.get() as i64)
+        .bind(user.id.get() as i64)
+        .fetch_optional(&ctx.data().db)
+        .await?;
+    let Some(role) = role else {
+        ctx.say(format!("User {} not muted!", user.name)).await?;
+        return Ok(());
+    };
+    lift_mute(ctx.http(), &ctx.data().db, guild_id, user.id, serenity::RoleId::new(role as u64)).await?;
+    case::record(ctx, user.id, "Unmute", None, None).await?;
+    ctx.say(format!("Successfully unmuted {}", user.mention())).await?;
+    Ok(())
+}
@@ -0,0 +1,544 @@
+use std::collections::{BTreeMap, BTreeSet};
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+use sqlx::PgPool;
+
+use crate::commands::moderation::channel::{restore_overwrites, StoredOverwrite};
+use crate::commands::moderation::modlog;
+use crate::Error;
+use crate::Context;
+
+/// Scheduled snapshots kept per guild, older ones are pruned
+const KEEP_SCHEDULED: i64 = 10;
+/// Permissions that are only usable together with SEND_MESSAGES
+const SENDS_MESSAGE: serenity::Permissions = serenity::Permissions::ATTACH_FILES
+    .union(serenity::Permissions::EMBED_LINKS)
+    .union(serenity::Permissions::MENTION_EVERYONE)
+    .union(serenity::Permissions::SEND_TTS_MESSAGES);
+/// Permissions explained by `/perms check`
+const KEY_PERMISSIONS: [serenity::Permissions; 12] = [
+    serenity::Permissions::VIEW_CHANNEL,
+    serenity::Permissions::SEND_MESSAGES,
+    serenity::Permissions::SEND_MESSAGES_IN_THREADS,
+    serenity::Permissions::READ_MESSAGE_HISTORY,
+    serenity::Permissions::ADD_REACTIONS,
+    serenity::Permissions::ATTACH_FILES,
+    serenity::Permissions::EMBED_LINKS,
+    serenity::Permissions::MENTION_EVERYONE,
+    serenity::Permissions::MANAGE_MESSAGES,
+    serenity::Permissions::CONNECT,
+    serenity::Permissions::SPEAK,
+    serenity::Permissions::MANAGE_ROLES
+];
+
+/// A channel's overwrites, either as saved in a snapshot or as they are now
+struct ChannelOverwrites {
+    name: String,
+    overwrites: Vec<StoredOverwrite>
+}
+
+impl ChannelOverwrites {
+    fn new(channel: &serenity::GuildChannel) -> Self {
+        let mut overwrites: Vec<StoredOverwrite> = channel.permission_overwrites.iter().filter_map(StoredOverwrite::new).collect();
+        overwrites.sort_by_key(|ovr| (ovr.target_type.clone(), ovr.target_id));
+        Self { name: channel.name.clone(), overwrites }
+    }
+
+    fn get(&self, target_type: &str, target_id: i64) -> Option<&StoredOverwrite> {
+        self.overwrites.iter().find(|ovr| ovr.target_type == target_type && ovr.target_id == target_id)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SnapshotOverwrite {
+    channel_id: i64,
+    #[sqlx(flatten)]
+    overwrite: StoredOverwrite
+}
+
+#[derive(sqlx::FromRow)]
+struct Snapshot {
+    id: i64,
+    created_by: Option<i64>,
+    created_at: i64
+}
+
+/// Save the overwrites of every channel in `channels` as a new snapshot, returning its ID
+pub async fn take_snapshot(
+    db: &PgPool,
+    guild_id: serenity::GuildId,
+    channels: &[serenity::GuildChannel],
+    created_by: Option<serenity::UserId>
+) -> Result<i64, Error> {
+    let mut tx = db.begin().await?;
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO permission_snapshots (guild_id, created_by, created_at) VALUES ($1, $2, $3) RETURNING id"
+    )
+    .bind(guild_id.get() as i64)
+    .bind(created_by.map(|user| user.get() as i64))
+    .bind(serenity::Timestamp::now().unix_timestamp())
+    .fetch_one(&mut *tx)
+    .await?;
+    for channel in channels {
+        sqlx::query("INSERT INTO snapshot_channels (snapshot_id, channel_id, name) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(channel.id.get() as i64)
+            .bind(&channel.name)
+            .execute(&mut *tx)
+            .await?;
+        for overwrite in channel.permission_overwrites.iter().filter_map(StoredOverwrite::new) {
+            sqlx::query(
+                "INSERT INTO snapshot_overwrites (snapshot_id, channel_id, target_type, target_id, allow, deny)
+                VALUES ($1, $2, $3, $4, $5, $6)"
+            )
+            .bind(id)
+            .bind(channel.id.get() as i64)
+            .bind(overwrite.target_type)
+            .bind(overwrite.target_id)
+            .bind(overwrite.allow)
+            .bind(overwrite.deny)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Delete all but the newest `KEEP_SCHEDULED` scheduled snapshots of a guild
+pub async fn prune_snapshots(db: &PgPool, guild_id: serenity::GuildId) -> Result<(), Error> {
+    sqlx::query(
+        "DELETE FROM permission_snapshots WHERE guild_id = $1 AND created_by IS NULL AND id NOT IN (
+            SELECT id FROM permission_snapshots WHERE guild_id = $1 AND created_by IS NULL ORDER BY id DESC LIMIT $2
+        )"
+    )
+    .bind(guild_id.get() as i64)
+    .bind(KEEP_SCHEDULED)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Load a snapshot of the current guild, `None` if it doesn't exist
+async fn load_snapshot(ctx: Context<'_>, id: i64) -> Result<Option<BTreeMap<serenity::ChannelId, ChannelOverwrites>>, Error> {
+    let db = &ctx.data().db;
+    let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM permission_snapshots WHERE id = $1 AND guild_id = $2")
+        .bind(id)
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .fetch_optional(db)
+        .await?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let channels: Vec<(i64, String)> = sqlx::query_as("SELECT channel_id, name FROM snapshot_channels WHERE snapshot_id = $1")
+        .bind(id)
+        .fetch_all(db)
+        .await?;
+    let overwrites: Vec<SnapshotOverwrite> = sqlx::query_as(
+        "SELECT channel_id, target_type, target_id, allow, deny FROM snapshot_overwrites
+        WHERE snapshot_id = $1 ORDER BY target_type, target_id"
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await?;
+    let mut snapshot: BTreeMap<serenity::ChannelId, ChannelOverwrites> = channels
+        .into_iter()
+        .map(|(channel, name)| (serenity::ChannelId::new(channel as u64), ChannelOverwrites { name, overwrites: Vec::new() }))
+        .collect();
+    for row in overwrites {
+        if let Some(channel) = snapshot.get_mut(&serenity::ChannelId::new(row.channel_id as u64)) {
+            channel.overwrites.push(row.overwrite);
+        }
+    }
+    Ok(Some(snapshot))
+}
+
+/// The overwrites of every channel of the current guild as they are now
+fn current_overwrites(ctx: Context<'_>) -> BTreeMap<serenity::ChannelId, ChannelOverwrites> {
+    ctx.guild()
+        .unwrap()
+        .channels
+        .values()
+        .map(|channel| (channel.id, ChannelOverwrites::new(channel)))
+        .collect()
+}
+
+/// Name the target of an overwrite in plain text, for places mentions don't render
+fn target_name(guild: &serenity::Guild, kind: serenity::PermissionOverwriteType) -> String {
+    match kind {
+        serenity::PermissionOverwriteType::Role(role) => match guild.roles.get(&role) {
+            Some(role) if role.id == guild.id.everyone_role() => String::from("@everyone"),
+            Some(role) => format!("@{}", role.name),
+            None => format!("deleted role {}", role)
+        },
+        serenity::PermissionOverwriteType::Member(user) => match guild.members.get(&user) {
+            Some(member) => format!("@{}", member.user.name),
+            None => format!("user {}", user)
+        },
+        _ => String::from("Unknown")
+    }
+}
+
+fn permission_names(perms: i64) -> String {
+    serenity::Permissions::from_bits_truncate(perms as u64).get_permission_names().join(", ")
+}
+
+/// Describe how the overwrites of a channel changed, one entry per target so a channel with many
+/// changed overwrites can be split across pages
+fn diff_overwrites(guild: &serenity::Guild, old: &ChannelOverwrites, new: &ChannelOverwrites) -> Vec<String> {
+    let targets: BTreeSet<(&str, i64)> = old
+        .overwrites
+        .iter()
+        .chain(new.overwrites.iter())
+        .map(|ovr| (ovr.target_type.as_str(), ovr.target_id))
+        .collect();
+    let mut lines = Vec::new();
+    for (target_type, target_id) in targets {
+        let (old, new) = (old.get(target_type, target_id), new.get(target_type, target_id));
+        let Some(kind) = old.or(new).and_then(StoredOverwrite::overwrite).map(|ovr| ovr.kind) else {
+            continue;
+        };
+        let target = target_name(guild, kind);
+        let (old_allow, old_deny) = old.map_or((0, 0), |ovr| (ovr.allow, ovr.deny));
+        let (new_allow, new_deny) = new.map_or((0, 0), |ovr| (ovr.allow, ovr.deny));
+        let mut changes: Vec<String> = [
+            ("now allows", new_allow & !old_allow),
+            ("no longer allows", old_allow & !new_allow),
+            ("now denies", new_deny & !old_deny),
+            ("no longer denies", old_deny & !new_deny)
+        ]
+        .into_iter()
+        .filter(|(_, perms)| *perms != 0)
+        .map(|(change, perms)| format!("  {} {}", change, permission_names(perms)))
+        .collect();
+        let header = match (old, new) {
+            (Some(_), None) => format!("- {}: overwrite removed", target),
+            (None, Some(_)) => format!("+ {}: overwrite added", target),
+            _ if changes.is_empty() => continue,
+            _ => format!("~ {}:", target)
+        };
+        changes.insert(0, header);
+        lines.push(changes.join("\n"));
+    }
+    lines
+}
+
+/// Work out each of `KEY_PERMISSIONS` for a member or role the way Discord does - role permissions first,
+/// then the @everyone overwrite, the overwrites of the roles and finally the member's own overwrite -
+/// explaining which step decided it
+fn explain_permissions(
+    guild: &serenity::Guild,
+    channel: Option<&serenity::GuildChannel>,
+    user: Option<serenity::UserId>,
+    roles: &[serenity::RoleId]
+) -> Vec<String> {
+    let everyone = guild.id.everyone_role();
+    let role_name = |role: serenity::RoleId| target_name(guild, serenity::PermissionOverwriteType::Role(role));
+    if user == Some(guild.owner_id) {
+        return vec![String::from("Server owner - has every permission")];
+    }
+    let roles: Vec<&serenity::Role> = std::iter::once(&everyone).chain(roles).filter_map(|role| guild.roles.get(role)).collect();
+    if let Some(admin) = roles.iter().find(|role| role.permissions.administrator()) {
+        return vec![format!("Administrator through {} - has every permission", role_name(admin.id))];
+    }
+    let overwrite = |kind: serenity::PermissionOverwriteType| {
+        channel.and_then(|channel| channel.permission_overwrites.iter().find(|ovr| ovr.kind == kind))
+    };
+    let role_overwrites: Vec<(serenity::RoleId, &serenity::PermissionOverwrite)> = roles
+        .iter()
+        .filter(|role| role.id != everyone)
+        .filter_map(|role| overwrite(serenity::PermissionOverwriteType::Role(role.id)).map(|ovr| (role.id, ovr)))
+        .collect();
+    let member_overwrite = user.and_then(|user| overwrite(serenity::PermissionOverwriteType::Member(user)));
+
+    let mut verdicts: Vec<(serenity::Permissions, bool, String)> = KEY_PERMISSIONS
+        .iter()
+        .map(|&perm| {
+            let (mut allowed, mut reason) = match roles.iter().find(|role| role.permissions.contains(perm)) {
+                Some(role) => (true, format!("granted by role {}", role_name(role.id))),
+                None => (false, String::from("not granted by any role"))
+            };
+            let mut apply = |ovr: &serenity::PermissionOverwrite, name: String| {
+                if ovr.deny.contains(perm) {
+                    (allowed, reason) = (false, format!("denied by the {} overwrite", name));
+                }
+                if ovr.allow.contains(perm) {
+                    (allowed, reason) = (true, format!("allowed by the {} overwrite", name));
+                }
+            };
+            if let Some(ovr) = overwrite(serenity::PermissionOverwriteType::Role(everyone)) {
+                apply(ovr, String::from("@everyone"));
+            }
+            // Role overwrites are combined before being applied, so any role allowing beats any role denying
+            if let Some((role, ovr)) = role_overwrites.iter().find(|(_, ovr)| ovr.deny.contains(perm)) {
+                apply(&serenity::PermissionOverwrite { allow: serenity::Permissions::empty(), ..(*ovr).clone() }, role_name(*role));
+            }
+            if let Some((role, ovr)) = role_overwrites.iter().find(|(_, ovr)| ovr.allow.contains(perm)) {
+                apply(&serenity::PermissionOverwrite { deny: serenity::Permissions::empty(), ..(*ovr).clone() }, role_name(*role));
+            }
+            if let Some(ovr) = member_overwrite {
+                apply(ovr, String::from("member"));
+            }
+            (perm, allowed, reason)
+        })
+        .collect();
+    // Discord drops everything else without VIEW_CHANNEL, and everything that needs a message to be sent
+    // without SEND_MESSAGES
+    let implications = [
+        (serenity::Permissions::VIEW_CHANNEL, serenity::Permissions::all(), "can't see the channel"),
+        (serenity::Permissions::SEND_MESSAGES, SENDS_MESSAGE, "can't send messages")
+    ];
+    for (required, dependent, why) in implications {
+        if channel.is_none() || verdicts.iter().any(|(perm, allowed, _)| *perm == required && *allowed) {
+            continue;
+        }
+        for (perm, allowed, reason) in verdicts.iter_mut() {
+            if *allowed && *perm != required && dependent.contains(*perm) {
+                (*allowed, *reason) = (false, String::from(why));
+            }
+        }
+    }
+    verdicts
+        .into_iter()
+        .map(|(perm, allowed, reason)| {
+            let name = perm.get_permission_names().join(", ");
+            format!("{} **{}** - {}", if allowed { "✅" } else { "❌" }, name, reason)
+        })
+        .collect()
+}
+
+/// Split lines into pages short enough for a single message and page through them - a line is never split
+async fn paginate_lines(ctx: Context<'_>, header: &str, lines: Vec<String>) -> Result<(), Error> {
+    let mut pages = vec![String::from(header)];
+    for line in lines {
+        if pages.last().unwrap().len() + line.len() > 1800 {
+            pages.push(String::from(header));
+        }
+        let page = pages.last_mut().unwrap();
+        page.push('\n');
+        page.push_str(&line);
+    }
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// Base command for channel permission snapshots
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    subcommands("check", "snapshot", "snapshots", "diff", "restore"),
+    guild_only = true
+    )
+]
+pub async fn perms(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Explain the effective permissions of a member or role in a channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn check(
+    ctx: Context<'_>,
+    #[description = "Member to check"] member: Option<serenity::Member>,
+    #[description = "Role to check"] role: Option<serenity::Role>,
+    #[description = "Channel to check in - defaults to current channel"] channel: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    let channel = match channel {
+        Some(channel) => channel,
+        None => ctx.guild_channel().await.unwrap()
+    };
+    let (title, user, roles, timed_out) = match (&member, &role) {
+        (Some(member), None) => (
+            member.user.name.clone(),
+            Some(member.user.id),
+            member.roles.clone(),
+            member.communication_disabled_until.is_some_and(|until| until > serenity::Timestamp::now())
+        ),
+        (None, Some(role)) => (format!("@{}", role.name), None, vec![role.id], false),
+        _ => {
+            ctx.say("Give either a member or a role to check").await?;
+            return Ok(());
+        }
+    };
+    let mut description = {
+        let guild = ctx.guild().unwrap();
+        let parent = channel.parent_id.and_then(|parent| guild.channels.get(&parent)).filter(|_| channel.thread_metadata.is_some());
+        explain_permissions(&guild, Some(parent.unwrap_or(&channel)), user, &roles).join("\n")
+    };
+    if timed_out {
+        description.push_str("\n\nTimed out - can only view channels and read message history until the timeout ends");
+    }
+    let embed = serenity::CreateEmbed::new()
+        .title(format!("Permissions of {} in #{}", title, channel.name))
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+
+/// Save the permission overwrites of every channel and category in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn snapshot(ctx: Context<'_>) -> Result<(), Error> {
+    let channels: Vec<serenity::GuildChannel> = ctx.guild().unwrap().channels.values().cloned().collect();
+    let id = take_snapshot(&ctx.data().db, ctx.guild_id().unwrap(), &channels, Some(ctx.author().id)).await?;
+    ctx.say(format!("Saved snapshot #{} of {} channels", id, channels.len())).await?;
+    Ok(())
+}
+
+
+/// List the permission snapshots of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn snapshots(ctx: Context<'_>) -> Result<(), Error> {
+    let snapshots: Vec<Snapshot> = sqlx::query_as(
+        "SELECT id, created_by, created_at FROM permission_snapshots WHERE guild_id = $1 ORDER BY id DESC"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .fetch_all(&ctx.data().db)
+    .await?;
+    if snapshots.is_empty() {
+        ctx.say("No snapshots saved yet").await?;
+        return Ok(());
+    }
+    let lines = snapshots
+        .iter()
+        .map(|snapshot| {
+            let author = match snapshot.created_by {
+                Some(user) => format!("by {}", serenity::UserId::new(user as u64).mention()),
+                None => String::from("scheduled")
+            };
+            format!("**#{}** - <t:{}:f> - {}", snapshot.id, snapshot.created_at, author)
+        })
+        .collect();
+    paginate_lines(ctx, &format!("Permission snapshots ({} total)", snapshots.len()), lines).await
+}
+
+
+/// Show what changed in channel permissions since a snapshot
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn diff(
+    ctx: Context<'_>,
+    #[description = "Snapshot to compare against"] snapshot: i64,
+    #[description = "Only compare this channel"] channel: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    let Some(saved) = load_snapshot(ctx, snapshot).await? else {
+        ctx.say(format!("Snapshot #{} doesn't exist", snapshot)).await?;
+        return Ok(());
+    };
+    let guild = ctx.guild().unwrap().clone();
+    let current = current_overwrites(ctx);
+    let channels: BTreeSet<serenity::ChannelId> = saved
+        .keys()
+        .chain(current.keys())
+        .filter(|id| channel.as_ref().is_none_or(|channel| channel.id == **id))
+        .copied()
+        .collect();
+    let mut lines = Vec::new();
+    for id in channels {
+        match (saved.get(&id), current.get(&id)) {
+            (Some(old), Some(new)) => {
+                let changes = diff_overwrites(&guild, old, new);
+                if !changes.is_empty() {
+                    lines.push(format!("**{}**", id.mention()));
+                    lines.extend(changes.into_iter().map(|change| format!("```diff\n{}\n```", change)));
+                }
+            }
+            (Some(old), None) => lines.push(format!("**#{}** ({}) was deleted", old.name, id)),
+            (None, Some(_)) => lines.push(format!("**{}** was created after the snapshot", id.mention())),
+            (None, None) => {}
+        }
+    }
+    if lines.is_empty() {
+        ctx.say(format!("Nothing changed since snapshot #{}", snapshot)).await?;
+        return Ok(());
+    }
+    paginate_lines(ctx, &format!("Changes since snapshot #{}", snapshot), lines).await
+}
+
+
+/// Roll channel permissions back to a snapshot, saving the current ones as a new snapshot first
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn restore(
+    ctx: Context<'_>,
+    #[description = "Snapshot to restore"] snapshot: i64,
+    #[description = "Only restore this channel"] channel: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    let Some(saved) = load_snapshot(ctx, snapshot).await? else {
+        ctx.say(format!("Snapshot #{} doesn't exist", snapshot)).await?;
+        return Ok(());
+    };
+    if let Some(channel) = &channel {
+        if !saved.contains_key(&channel.id) {
+            ctx.say(format!("{} isn't part of snapshot #{}", channel.mention(), snapshot)).await?;
+            return Ok(());
+        }
+    }
+    let current = current_overwrites(ctx);
+    let changed: Vec<(serenity::ChannelId, &ChannelOverwrites)> = saved
+        .iter()
+        .filter(|(id, _)| channel.as_ref().is_none_or(|channel| channel.id == **id))
+        .filter_map(|(id, old)| {
+            let new = current.get(id)?;
+            (old.overwrites != new.overwrites).then_some((*id, old))
+        })
+        .collect();
+    if changed.is_empty() {
+        ctx.say(format!("Nothing to restore, permissions match snapshot #{}", snapshot)).await?;
+        return Ok(());
+    }
+
+    let channels: Vec<serenity::GuildChannel> = ctx.guild().unwrap().channels.values().cloned().collect();
+    let backup = take_snapshot(&ctx.data().db, ctx.guild_id().unwrap(), &channels, Some(ctx.author().id)).await?;
+    let reply = ctx.say(format!("Saved current permissions as snapshot #{}, restoring {} channels", backup, changed.len())).await?;
+    let mut failed = 0;
+    for (id, old) in &changed {
+        if let Err(e) = restore_overwrites(ctx.http(), *id, &old.overwrites).await {
+            tracing::warn!("Failed to restore {} to snapshot #{}: {}", id, snapshot, e);
+            failed += 1;
+        }
+    }
+    let mut summary = format!("Restored {} channels to snapshot #{}", changed.len() - failed, snapshot);
+    if failed != 0 {
+        summary.push_str(&format!(", failed to restore {}", failed));
+    }
+    summary.push_str(&format!(" - snapshot #{} has the permissions from before", backup));
+    reply.edit(ctx, poise::CreateReply::default().content(&summary)).await?;
+    let mut entry = modlog::LogEntry::new("Permissions Restore").details(summary);
+    if let Some(channel) = &channel {
+        entry = entry.channel(channel.id);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
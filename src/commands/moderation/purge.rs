@@ -1,17 +1,361 @@
+pub mod filter;
+pub mod transcript;
+
+use humantime::{format_duration, parse_duration};
 use poise::serenity_prelude as serenity;
-use regex;
+use poise::serenity_prelude::Mentionable;
 // use poise::CreateReply;
 
 
+use crate::commands::moderation::modlog;
+use filter::{text_condition, Condition, Filter};
 use crate::Error;
 use crate::Context;
 
+/// Most messages a single purge may search through
+const MAX_SCAN: u16 = 5000;
+/// Messages older than this can't be bulk deleted, keeps a minute of leeway for slow purges
+const BULK_DELETE_MAX_AGE: i64 = 14 * 24 * 60 * 60 - 60;
+/// Most messages a single purge deletes one by one, the rest are skipped so the purge finishes
+/// well within the lifetime of the interaction
+const MAX_SINGLE_DELETES: usize = 100;
+
+/// How a set of messages ended up being deleted
+#[derive(Default)]
+struct PurgeReport {
+    bulk: usize,
+    single: usize,
+    skipped: usize,
+    failed: usize,
+    /// One by one deletes attempted so far, counted against `MAX_SINGLE_DELETES`
+    attempted: usize
+}
+
+impl PurgeReport {
+    fn deleted(&self) -> usize {
+        self.bulk + self.single
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("Purged {} messages", self.deleted());
+        if self.single != 0 {
+            summary.push_str(&format!(", {} of them older than 14 days were deleted one by one", self.single));
+        }
+        if self.skipped != 0 {
+            summary.push_str(&format!(
+                ", skipped {} older than 14 days - run the purge again to delete more of them",
+                self.skipped
+            ));
+        }
+        if self.failed != 0 {
+            summary.push_str(&format!(", failed to delete {}", self.failed));
+        }
+        summary
+    }
+}
+
+/// The number of messages to purge/search, falling back to the server default
+async fn resolve_amount(ctx: Context<'_>, amount: Option<u16>) -> Result<Option<u16>, Error> {
+    let amount = match amount {
+        Some(amount) => amount,
+        None => ctx.data().config.get(&ctx.data().db, ctx.guild_id().unwrap()).await?.default_purge() as u16
+    };
+    if amount > MAX_SCAN {
+        ctx.say(format!("A purge can search at most {} messages", MAX_SCAN)).await?;
+        return Ok(None);
+    }
+    Ok(Some(amount))
+}
+
+/// Fetch up to `limit` messages in `channel` sent before `before` and after `after` (both exclusive),
+/// newest first, a page at a time
+async fn fetch_messages(
+    ctx: Context<'_>,
+    channel: serenity::ChannelId,
+    limit: u16,
+    mut before: Option<serenity::MessageId>,
+    after: Option<serenity::MessageId>
+) -> Result<Vec<serenity::Message>, Error> {
+    let mut messages: Vec<serenity::Message> = Vec::new();
+    while messages.len() < limit as usize {
+        let page_size = (limit as usize - messages.len()).min(100) as u8;
+        let mut request = serenity::GetMessages::new().limit(page_size);
+        if let Some(before) = before {
+            request = request.before(before);
+        }
+        let mut page = channel.messages(ctx.http(), request).await?;
+        let mut exhausted = page.len() < page_size as usize;
+        before = page.last().map(|msg| msg.id);
+        if let Some(after) = after {
+            let len = page.len();
+            page.retain(|msg| msg.id > after);
+            exhausted |= page.len() < len;
+        }
+        messages.extend(page);
+        if exhausted {
+            break;
+        }
+    }
+    Ok(messages)
+}
+
+/// Upper bound that keeps messages sent after the command was invoked, like its own deferred reply, out of a purge
+fn invoked_before(ctx: Context<'_>) -> serenity::MessageId {
+    serenity::MessageId::new(ctx.id() + 1)
+}
+
+/// The ID a message sent at the given unix timestamp would have, for use as a range bound
+fn snowflake_at(timestamp: i64) -> serenity::MessageId {
+    const DISCORD_EPOCH: i64 = 1_420_070_400_000;
+    serenity::MessageId::new(((timestamp * 1000 - DISCORD_EPOCH).max(1) as u64) << 22)
+}
+
+/// Delete messages, adding the outcome to `report` - bulk deletes everything it can and deletes messages
+/// too old for that one by one, up to `MAX_SINGLE_DELETES` across the whole purge
+async fn delete_messages(
+    ctx: Context<'_>,
+    channel: serenity::ChannelId,
+    messages: &[serenity::MessageId],
+    report: &mut PurgeReport
+) -> Result<(), Error> {
+    let cutoff = serenity::Timestamp::now().unix_timestamp() - BULK_DELETE_MAX_AGE;
+    let (recent, old): (Vec<serenity::MessageId>, Vec<serenity::MessageId>) = messages
+        .iter()
+        .partition(|id| id.created_at().unix_timestamp() > cutoff);
+    for chunk in recent.chunks(100) {
+        match channel.delete_messages(ctx.http(), chunk).await {
+            Ok(_) => report.bulk += chunk.len(),
+            Err(e) => {
+                tracing::warn!("Failed to bulk delete {} messages in {}: {}", chunk.len(), channel, e);
+                report.failed += chunk.len();
+            }
+        }
+    }
+    for id in old {
+        if report.attempted >= MAX_SINGLE_DELETES {
+            report.skipped += 1;
+            continue;
+        }
+        report.attempted += 1;
+        match channel.delete_message(ctx.http(), id).await {
+            Ok(_) => report.single += 1,
+            Err(_) => report.failed += 1
+        }
+    }
+    Ok(())
+}
+
+/// Whether two reactions are the same emoji - custom emojis are compared by ID only
+fn same_emoji(a: &serenity::ReactionType, b: &serenity::ReactionType) -> bool {
+    match (a, b) {
+        (serenity::ReactionType::Custom { id: a, .. }, serenity::ReactionType::Custom { id: b, .. }) => a == b,
+        (a, b) => a == b
+    }
+}
+
+/// Reply with what a purge would delete instead of deleting it
+async fn send_preview(ctx: Context<'_>, header: &str, messages: &[serenity::Message]) -> Result<(), Error> {
+    let mut files = transcript::attachments(ctx.author(), messages);
+    let mut content = transcript::preview(header, messages);
+    if files.len() > 1 {
+        content.push_str(&format!("\nThe transcript is split into {} files, only the first is attached", files.len()));
+    }
+    let reply = poise::CreateReply::default()
+        .content(content)
+        .attachment(files.swap_remove(0))
+        .allowed_mentions(serenity::CreateAllowedMentions::new());
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Upload a transcript of `messages` to the mod-log before they're deleted - returns why that failed,
+/// in which case nothing may be deleted
+async fn upload_transcript(ctx: Context<'_>, content: String, messages: &[serenity::Message]) -> Option<String> {
+    match modlog::upload(ctx, content, transcript::attachments(ctx.author(), messages)).await {
+        Ok(()) => None,
+        Err(e) => {
+            tracing::warn!("Failed to upload a purge transcript to the mod-log: {}", e);
+            Some(format!("Failed to upload the transcript to the mod-log channel, nothing was purged: {}", e))
+        }
+    }
+}
+
+/// Search the last `amount` messages of the current channel and purge the ones matching `filter`
+async fn purge_matching(
+    ctx: Context<'_>,
+    amount: Option<u16>,
+    dry_run: Option<bool>,
+    entry: modlog::LogEntry,
+    filter: Filter
+) -> Result<(), Error> {
+    let Some(amount) = resolve_amount(ctx, amount).await? else {
+        return Ok(());
+    };
+    let messages = fetch_messages(ctx, ctx.channel_id(), amount, Some(invoked_before(ctx)), None).await?;
+    purge_messages(ctx, dry_run, entry, messages.into_iter().filter(|msg| filter.matches(msg)).collect()).await
+}
+
+/// Purge up to `MAX_SCAN` messages of the current channel between two bounds (both exclusive), newest first -
+/// says so if the range holds more than that
+async fn purge_range(
+    ctx: Context<'_>,
+    before: Option<serenity::MessageId>,
+    after: serenity::MessageId,
+    dry_run: Option<bool>,
+    entry: modlog::LogEntry
+) -> Result<(), Error> {
+    let before = before.unwrap_or_else(|| invoked_before(ctx)).min(invoked_before(ctx));
+    let messages = fetch_messages(ctx, ctx.channel_id(), MAX_SCAN, Some(before), Some(after)).await?;
+    let limited = messages.len() == MAX_SCAN as usize;
+    purge_messages(ctx, dry_run, entry, messages).await?;
+    if limited {
+        ctx.say(format!(
+            "Only the newest {} messages of the range were searched - narrow the range or run the purge again to reach older ones",
+            MAX_SCAN
+        )).await?;
+    }
+    Ok(())
+}
+
+/// Delete `messages` from the current channel, uploading a transcript of them to the mod-log first
+async fn purge_messages(
+    ctx: Context<'_>,
+    dry_run: Option<bool>,
+    entry: modlog::LogEntry,
+    messages: Vec<serenity::Message>
+) -> Result<(), Error> {
+    let channel = ctx.channel_id();
+    if messages.is_empty() {
+        ctx.say("No messages to purge").await?;
+        return Ok(());
+    }
+    if dry_run.unwrap_or_default() {
+        return send_preview(ctx, &format!("{} messages would be purged", messages.len()), &messages).await;
+    }
+    let content = format!("Transcript of {} messages purged from {}", messages.len(), channel.mention());
+    if let Some(error) = upload_transcript(ctx, content, &messages).await {
+        ctx.say(error).await?;
+        return Ok(());
+    }
+    let reply = ctx.say(format!("Purging {} messages", messages.len())).await?;
+    let ids: Vec<serenity::MessageId> = messages.iter().map(|msg| msg.id).collect();
+    let mut report = PurgeReport::default();
+    delete_messages(ctx, channel, &ids, &mut report).await?;
+    log_purge(ctx, entry, channel, report.deleted()).await?;
+    reply.edit(ctx, poise::CreateReply::default().content(report.summary())).await?;
+    Ok(())
+}
+
+/// Text channels and active threads of the current guild the bot can purge messages in
+async fn purgeable_channels(ctx: Context<'_>) -> Result<Vec<serenity::GuildChannel>, Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let bot = guild_id.member(ctx, ctx.framework().bot_id).await?;
+    let threads = guild_id.get_active_threads(ctx.http()).await?.threads;
+    let required = serenity::Permissions::VIEW_CHANNEL
+        | serenity::Permissions::READ_MESSAGE_HISTORY
+        | serenity::Permissions::MANAGE_MESSAGES;
+    let guild = ctx.guild().unwrap();
+    let can_purge = |channel: &serenity::GuildChannel| guild.user_permissions_in(channel, &bot).contains(required);
+    let mut channels: Vec<serenity::GuildChannel> = guild
+        .channels
+        .values()
+        .filter(|channel| matches!(
+            channel.kind,
+            serenity::ChannelType::Text | serenity::ChannelType::News | serenity::ChannelType::Voice
+        ))
+        .filter(|channel| can_purge(channel))
+        .cloned()
+        .collect();
+    channels.extend(threads.into_iter().filter(|thread| {
+        thread.parent_id.and_then(|parent| guild.channels.get(&parent)).is_some_and(can_purge)
+    }));
+    Ok(channels)
+}
+
+/// Update the progress of a server-wide purge - best effort, so the purge still finishes and gets logged
+/// once the interaction can no longer be edited
+async fn edit_progress(ctx: Context<'_>, reply: &poise::ReplyHandle<'_>, content: String) {
+    if let Err(e) = reply.edit(ctx, poise::CreateReply::default().content(content)).await {
+        tracing::warn!("Failed to update the progress of a server-wide purge: {}", e);
+    }
+}
+
+/// Purge a user's messages sent after `after` in every channel and active thread the bot can purge in
+async fn purge_everywhere(
+    ctx: Context<'_>,
+    user: &serenity::User,
+    after: serenity::MessageId,
+    dry_run: Option<bool>,
+    entry: modlog::LogEntry
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let channels = purgeable_channels(ctx).await?;
+    let reply = ctx.say(format!("Searching {} channels for messages by {}", channels.len(), user.name)).await?;
+    let mut found: Vec<(serenity::ChannelId, Vec<serenity::Message>)> = Vec::new();
+    for (i, channel) in channels.iter().enumerate() {
+        if channel.last_message_id.is_none_or(|last| last <= after) {
+            continue;
+        }
+        let messages = match fetch_messages(ctx, channel.id, MAX_SCAN, Some(invoked_before(ctx)), Some(after)).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                tracing::warn!("Failed to fetch messages in {} for a server-wide purge: {}", channel.id, e);
+                continue;
+            }
+        };
+        let messages: Vec<serenity::Message> = messages.into_iter().filter(|msg| msg.author.id == user.id).collect();
+        if !messages.is_empty() {
+            found.push((channel.id, messages));
+        }
+        let progress = format!(
+            "Searched {}/{} channels, found {} messages by {} so far",
+            i + 1,
+            channels.len(),
+            found.iter().map(|(_, messages)| messages.len()).sum::<usize>(),
+            user.name
+        );
+        edit_progress(ctx, &reply, progress).await;
+    }
+    let messages: Vec<serenity::Message> = found.iter().flat_map(|(_, messages)| messages.iter().cloned()).collect();
+    if messages.is_empty() {
+        edit_progress(ctx, &reply, String::from("No messages to purge")).await;
+        return Ok(());
+    }
+    if dry_run.unwrap_or_default() {
+        let header = format!("{} messages in {} channels would be purged", messages.len(), found.len());
+        return send_preview(ctx, &header, &messages).await;
+    }
+    let content = format!("Transcript of {} messages purged from {} channels", messages.len(), found.len());
+    if let Some(error) = upload_transcript(ctx, content, &messages).await {
+        edit_progress(ctx, &reply, error).await;
+        return Ok(());
+    }
+    let mut report = PurgeReport::default();
+    for (i, (channel, messages)) in found.iter().enumerate() {
+        let progress = format!("Purging {} messages, channel {}/{}", messages.len(), i + 1, found.len());
+        edit_progress(ctx, &reply, progress).await;
+        let ids: Vec<serenity::MessageId> = messages.iter().map(|msg| msg.id).collect();
+        delete_messages(ctx, *channel, &ids, &mut report).await?;
+    }
+    modlog::post(ctx, entry.details(format!("{} message(s) affected in {} channels", report.deleted(), found.len()))).await?;
+    edit_progress(ctx, &reply, format!("{} across {} channels", report.summary(), found.len())).await;
+    Ok(())
+}
+
+/// Post a finished purge to the mod-log
+async fn log_purge(ctx: Context<'_>, entry: modlog::LogEntry, channel: serenity::ChannelId, count: usize) -> Result<(), Error> {
+    modlog::post(ctx, entry.channel(channel).details(format!("{} message(s) affected", count))).await
+}
+
 /// Base command for purging messages
 #[poise::command(
     slash_command,
     prefix_command,
     required_permissions = "MANAGE_MESSAGES",
-    subcommands("all", "bot", "contains", "embeds", "files", "human", "links", "mentions", "reactions", "user"),
+    subcommands(
+        "after", "all", "between", "bot", "contains", "custom", "embeds", "endswith", "files",
+        "human", "links", "mentions", "reactions", "regex", "since", "startswith", "user"
+    ),
     guild_only = true
     )
 ]
@@ -21,7 +365,7 @@ pub async fn purge(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 
-/// Purge the last n messages - defaults to maximum 100
+/// Purge the last n messages - defaults to server default
 #[poise::command(
     slash_command,
     prefix_command,
@@ -31,30 +375,38 @@ pub async fn purge(ctx: Context<'_>) -> Result<(), Error> {
 ]
 pub async fn all(
     ctx: Context<'_>,
-    #[description = "No. of Messages to purge"] amount: Option<u8>
+    #[description = "No. of Messages to purge"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    if amount == 0 {return Ok(())}
-    let channel = ctx.guild_channel().await.unwrap();
-
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .map(|msg| msg.id)
-    .collect();
+    ctx.defer().await?;
+    purge_matching(ctx, amount, dry_run, modlog::LogEntry::new("Purge"), Filter::new()).await
+}
 
-    ctx.say(format!("Purging {} messages", amount)).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+
+/// Purges every message sent after the given message, up to 5000
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn after(
+    ctx: Context<'_>,
+    #[description = "Message to purge everything after (ID or link)"] message: serenity::Message,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    if message.channel_id != ctx.channel_id() {
+        ctx.say("The message has to be in this channel").await?;
+        return Ok(());
+    }
+    let entry = modlog::LogEntry::new("Purge After").reason(format!("After {}", message.link()));
+    purge_range(ctx, None, message.id, dry_run, entry).await
 }
 
 
-/// Purges all messages sent by a bot in the last n messages - defaults to maximum of 100
+/// Purges every message between two messages, including both of them, up to 5000
 #[poise::command(
     slash_command,
     prefix_command,
@@ -62,32 +414,55 @@ pub async fn all(
     guild_only = true
     )
 ]
-pub async fn bot(
+pub async fn between(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "First message of the range (ID or link)"] start: serenity::Message,
+    #[description = "Last message of the range (ID or link)"] end: serenity::Message,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
+    ctx.defer().await?;
+    if start.channel_id != ctx.channel_id() || end.channel_id != ctx.channel_id() {
+        ctx.say("Both messages have to be in this channel").await?;
+        return Ok(());
+    }
+    let (first, last) = if start.id <= end.id { (start, end) } else { (end, start) };
+    let entry = modlog::LogEntry::new("Purge Between")
+        .reason(format!("Between {} and {}", first.link(), last.link()));
+    purge_range(
+        ctx,
+        Some(serenity::MessageId::new(last.id.get() + 1)),
+        serenity::MessageId::new(first.id.get() - 1),
+        dry_run,
+        entry
+    ).await
+}
 
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.author.bot)
-    .map(|msg| msg.id)
-    .collect();
 
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+/// Purges every message sent within the given duration, e.g. 30m or 2h, up to 5000
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn since(
+    ctx: Context<'_>,
+    #[description = "How far back to purge, e.g. 30m or 2h"] time: String,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let Ok(duration) = parse_duration(&time) else {
+        ctx.say(format!("Invalid duration `{}`", time)).await?;
+        return Ok(());
+    };
+    let start = serenity::Timestamp::now().unix_timestamp() - duration.as_secs() as i64;
+    let entry = modlog::LogEntry::new("Purge Since").reason(format!("Sent in the last {}", format_duration(duration)));
+    purge_range(ctx, None, snowflake_at(start), dry_run, entry).await
 }
 
 
-/// Purges all messages with the given word/phrase in the last n messages - defaults to maximum of 100
+/// Purges all messages sent by a bot in the last n messages - defaults to server default
 #[poise::command(
     slash_command,
     prefix_command,
@@ -95,32 +470,49 @@ pub async fn bot(
     guild_only = true
     )
 ]
-pub async fn contains(
+pub async fn bot(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>,
-    #[description = "Word/Phrase to search for"] search: String
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
+    ctx.defer().await?;
+    purge_matching(ctx, amount, dry_run, modlog::LogEntry::new("Purge Bots"), Filter::new().with(Condition::Bot)).await
+}
 
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.content.contains(&search))
-    .map(|msg| msg.id)
-    .collect();
 
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+/// Purges all messages with the given word/phrase in the last n messages - defaults to server default
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn contains(
+    ctx: Context<'_>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "Word/Phrase to search for"] search: String,
+    #[description = "Whether to ignore case when matching - defaults to false"] ignore_case: Option<bool>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Contains").reason(format!("Containing `{}`", search)),
+        Filter::new().with(text_condition(Condition::Contains, &search, ignore_case.unwrap_or_default()))
+    ).await
 }
 
-/// Purges all messages with an embed in the last n messages - defaults to maximum of 100
+
+/// Purges all messages matching a filter expression in the last n messages
+///
+/// Terms are separated by spaces and can be negated with a leading `-`, all of them have to match:
+/// `user:<user>` (repeatable, any of them), `bot`, `has:link|file|embed|mention|reaction`,
+/// `contains:<text>`, `startswith:<text>`, `endswith:<text>`, `regex:<pattern>`,
+/// `after:<message>`, `before:<message>`, `within:<duration>`
+/// Use quotes for text with spaces, e.g. `contains:"free nitro" -bot within:1h`
 #[poise::command(
     slash_command,
     prefix_command,
@@ -128,32 +520,57 @@ pub async fn contains(
     guild_only = true
     )
 ]
-pub async fn embeds(
+pub async fn custom(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>,
+    #[description = "Filter expression, e.g. has:link -bot within:1h"] #[rest] expression: String
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
+    ctx.defer().await?;
+    let filter: Filter = match expression.parse() {
+        Ok(filter) => filter,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Custom").reason(format!("Matching `{}`", expression)),
+        filter
+    ).await
+}
 
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.embeds.len() != 0)
-    .map(|msg| msg.id)
-    .collect();
 
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+/// Purges all messages starting with the given word/phrase in the last n messages
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn startswith(
+    ctx: Context<'_>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "Word/Phrase messages start with"] search: String,
+    #[description = "Whether to ignore case when matching - defaults to false"] ignore_case: Option<bool>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Starts With").reason(format!("Starting with `{}`", search)),
+        Filter::new().with(text_condition(Condition::StartsWith, &search, ignore_case.unwrap_or_default()))
+    ).await
 }
 
 
-/// Purges all messages sent by a non bot in the last n messages - defaults to maximum of 100
+/// Purges all messages ending with the given word/phrase in the last n messages
 #[poise::command(
     slash_command,
     prefix_command,
@@ -161,31 +578,56 @@ pub async fn embeds(
     guild_only = true
     )
 ]
-pub async fn human(
+pub async fn endswith(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "Word/Phrase messages end with"] search: String,
+    #[description = "Whether to ignore case when matching - defaults to false"] ignore_case: Option<bool>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
+    ctx.defer().await?;
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Ends With").reason(format!("Ending with `{}`", search)),
+        Filter::new().with(text_condition(Condition::EndsWith, &search, ignore_case.unwrap_or_default()))
+    ).await
+}
 
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| !msg.author.bot)
-    .map(|msg| msg.id)
-    .collect();
 
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+/// Purges all messages matching a regular expression in the last n messages
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn regex(
+    ctx: Context<'_>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>,
+    #[description = "Regular expression to match"] #[rest] pattern: String
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let regex = match regex::RegexBuilder::new(&pattern).size_limit(1 << 20).build() {
+        Ok(regex) => regex,
+        Err(e) => {
+            ctx.say(format!("Invalid regular expression `{}`:\n```\n{}\n```", pattern, e)).await?;
+            return Ok(());
+        }
+    };
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Regex").reason(format!("Matching `{}`", pattern)),
+        Filter::new().with(Condition::Regex(regex))
+    ).await
 }
 
-/// Purges all messages with attachments in the last n messages - defaults to maximum of 100
+/// Purges all messages with an embed in the last n messages - defaults to server default
 #[poise::command(
     slash_command,
     prefix_command,
@@ -193,32 +635,52 @@ pub async fn human(
     guild_only = true
     )
 ]
-pub async fn files(
+pub async fn embeds(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
+    ctx.defer().await?;
+    purge_matching(ctx, amount, dry_run, modlog::LogEntry::new("Purge Embeds"), Filter::new().with(Condition::Embed)).await
+}
 
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.attachments.len() != 0)
-    .map(|msg| msg.id)
-    .collect();
 
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+/// Purges all messages sent by a non bot in the last n messages - defaults to server default
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn human(
+    ctx: Context<'_>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    purge_matching(ctx, amount, dry_run, modlog::LogEntry::new("Purge Humans"), Filter::new().without(Condition::Bot)).await
 }
 
+/// Purges all messages with attachments in the last n messages - defaults to server default
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_MESSAGES",
+    guild_only = true
+    )
+]
+pub async fn files(
+    ctx: Context<'_>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    purge_matching(ctx, amount, dry_run, modlog::LogEntry::new("Purge Files"), Filter::new().with(Condition::File)).await
+}
 
-/// Purges all reactions in the last n messages - defaults to maximum of 100
+
+/// Purges reactions in the last n messages, optionally only one emoji and/or one user's reactions
 #[poise::command(
     slash_command,
     prefix_command,
@@ -228,28 +690,102 @@ pub async fn files(
 ]
 pub async fn reactions(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>,
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "Only remove this emoji"] emoji: Option<String>,
+    #[description = "Only remove reactions by this user"] user: Option<serenity::User>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
-
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
+    ctx.defer().await?;
+    let emoji = match emoji.as_deref().map(|emoji| serenity::ReactionType::try_from(emoji.trim())) {
+        Some(Ok(emoji)) => Some(emoji),
+        Some(Err(_)) => {
+            ctx.say(format!("Invalid emoji `{}`", emoji.unwrap())).await?;
+            return Ok(());
+        }
+        None => None
+    };
+    let Some(amount) = resolve_amount(ctx, amount).await? else {
+        return Ok(());
+    };
+    let channel = ctx.channel_id();
+    let wanted = |reaction: &&serenity::MessageReaction| {
+        emoji.as_ref().is_none_or(|emoji| same_emoji(&reaction.reaction_type, emoji))
+    };
+
+    let mut messages: Vec<serenity::Message> = fetch_messages(ctx, channel, amount, None, None)
+    .await?
     .into_iter()
-    .filter_map(|msg| if msg.reactions.len() != 0 {Some(msg.id)} else {None})
+    .filter(|msg| msg.reactions.iter().any(|reaction| wanted(&reaction)))
     .collect();
+    // With a user given, keep only the reactions that user added, so the counts only cover their messages
+    if let Some(user) = &user {
+        for msg in &mut messages {
+            let mut reacted = Vec::new();
+            for reaction in msg.reactions.iter().filter(wanted) {
+                // Users are listed by ID, so the first user after `user.id - 1` is `user` if they reacted
+                let by_user = channel
+                    .reaction_users(
+                        ctx.http(),
+                        msg.id,
+                        reaction.reaction_type.clone(),
+                        Some(1),
+                        serenity::UserId::new(user.id.get() - 1)
+                    )
+                    .await?
+                    .first()
+                    .is_some_and(|reactor| reactor.id == user.id);
+                if by_user {
+                    reacted.push(reaction.clone());
+                }
+            }
+            msg.reactions = reacted;
+        }
+        messages.retain(|msg| !msg.reactions.is_empty());
+    }
 
-    ctx.say(format!("Purging reactions in {} messages", &messages.len())).await?;
-    for msgid in &messages {
-        channel.delete_reactions(&ctx.http(), msgid).await?;
+    if dry_run.unwrap_or_default() {
+        let header = format!("{} messages would have reactions removed", messages.len());
+        return send_preview(ctx, &header, &messages).await;
     }
-    Ok(())
+    let reply = ctx.say(format!("Purging reactions in {} messages", &messages.len())).await?;
+    let mut removed: u64 = 0;
+    let mut affected: usize = 0;
+    for msg in &messages {
+        let before = removed;
+        match (&emoji, &user) {
+            (None, None) => {
+                channel.delete_reactions(ctx.http(), msg.id).await?;
+                removed += msg.reactions.iter().map(|reaction| reaction.count).sum::<u64>();
+            }
+            (Some(emoji), None) => {
+                channel.delete_reaction_emoji(ctx.http(), msg.id, emoji.clone()).await?;
+                removed += msg.reactions.iter().filter(wanted).map(|reaction| reaction.count).sum::<u64>();
+            }
+            (_, Some(user)) => {
+                for reaction in &msg.reactions {
+                    channel.delete_reaction(ctx.http(), msg.id, Some(user.id), reaction.reaction_type.clone()).await?;
+                    removed += 1;
+                }
+            }
+        }
+        if removed != before {
+            affected += 1;
+        }
+    }
+    let summary = format!("Removed {} reactions from {} messages", removed, affected);
+    reply.edit(ctx, poise::CreateReply::default().content(summary.clone())).await?;
+    let mut entry = modlog::LogEntry::new("Purge Reactions").channel(channel).details(summary);
+    if let Some(user) = &user {
+        entry = entry.target(format!("{} ({})", user.mention(), user.id));
+    }
+    if let Some(emoji) = &emoji {
+        entry = entry.reason(format!("Only {}", emoji));
+    }
+    modlog::post(ctx, entry).await
 }
 
 
-/// Purges all messages sent by a user in the last n messages - defaults to maximum of 100
+/// Purges all messages sent by a user in the last n messages, or everywhere in a time window
 #[poise::command(
     slash_command,
     prefix_command,
@@ -259,31 +795,38 @@ pub async fn reactions(
 ]
 pub async fn user(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>,
-    #[description = "User whose messages are to be purged"] user: serenity::User
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "User whose messages are to be purged"] user: serenity::User,
+    #[description = "Only purge messages sent within this duration, e.g. 30m or 2h"] within: Option<String>,
+    #[description = "Purge in every channel and thread - requires within"] everywhere: Option<bool>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
-
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.author == user)
-    .map(|msg| msg.id)
-    .collect();
-
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+    ctx.defer().await?;
+    let start = match within.as_deref().map(parse_duration) {
+        Some(Ok(duration)) => Some(serenity::Timestamp::now().unix_timestamp() - duration.as_secs() as i64),
+        Some(Err(_)) => {
+            ctx.say(format!("Invalid duration `{}`", within.unwrap())).await?;
+            return Ok(());
+        }
+        None => None
+    };
+    let entry = modlog::LogEntry::new("Purge User").target(format!("{} ({})", user.mention(), user.id));
+    if everywhere.unwrap_or_default() {
+        let Some(start) = start else {
+            ctx.say("A server-wide purge needs a time window, e.g. `within: 1h`").await?;
+            return Ok(());
+        };
+        return purge_everywhere(ctx, &user, snowflake_at(start), dry_run, entry).await;
+    }
+    let mut filter = Filter::new().with(Condition::User(user.id));
+    if let Some(start) = start {
+        filter = filter.with(Condition::Since(start));
+    }
+    purge_matching(ctx, amount, dry_run, entry, filter).await
 }
 
 
-/// Purges all messages containing user/role pings in the last n messages - defaults to maximum of 100
+/// Purges all messages containing user/role pings in the last n messages - defaults to server default
 #[poise::command(
     slash_command,
     prefix_command,
@@ -293,30 +836,21 @@ pub async fn user(
 ]
 pub async fn mentions(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
-
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| msg.mention_roles.len() != 0 || msg.mentions.len() != 0)
-    .map(|msg| msg.id)
-    .collect();
-
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+    ctx.defer().await?;
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Mentions"),
+        Filter::new().with(Condition::Mention)
+    ).await
 }
 
 
-/// Purges all messages containing links in the last n messages - defaults to maximum of 100
+/// Purges all messages containing links in the last n messages - defaults to server default
 #[poise::command(
     slash_command,
     prefix_command,
@@ -326,24 +860,15 @@ pub async fn mentions(
 ]
 pub async fn links(
     ctx: Context<'_>,
-    #[description = "No. of Messages to search"] amount: Option<u8>
+    #[description = "No. of Messages to search"] #[max = 5000] amount: Option<u16>,
+    #[description = "List what would be purged without deleting anything"] dry_run: Option<bool>
 ) -> Result<(), Error> {
-    let amount = amount.unwrap_or_else(|| 100);
-    let channel = ctx.guild_channel().await.unwrap();
-
-    let messages: Vec<serenity::MessageId> = channel.messages(
-        &ctx.http(),
-        serenity::GetMessages::new().limit(amount)
-    ).await?
-    .into_iter()
-    .filter(|msg| regex::Regex::new("https?://S+").unwrap().is_match(&msg.content))
-    .map(|msg| msg.id)
-    .collect();
-
-    ctx.say(format!("Purging {} messages", messages.len())).await?;
-    channel.delete_messages(
-        &ctx.http(),
-        messages
-    ).await?;
-    Ok(())
+    ctx.defer().await?;
+    purge_matching(
+        ctx,
+        amount,
+        dry_run,
+        modlog::LogEntry::new("Purge Links"),
+        Filter::new().with(Condition::Link)
+    ).await
 }
@@ -0,0 +1,277 @@
+use std::str::FromStr;
+use humantime::parse_duration;
+use once_cell::sync::Lazy;
+use poise::serenity_prelude as serenity;
+
+/// Matches http(s) links anywhere in a message
+static LINK_REGEX: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"https?://\S+").unwrap());
+
+/// A single check a message can be tested against
+pub enum Condition {
+    User(serenity::UserId),
+    Bot,
+    Link,
+    File,
+    Embed,
+    Mention,
+    Reaction,
+    Contains(String, bool),
+    StartsWith(String, bool),
+    EndsWith(String, bool),
+    Regex(regex::Regex),
+    After(serenity::MessageId),
+    Before(serenity::MessageId),
+    /// Sent at or after the given unix timestamp
+    Since(i64)
+}
+
+impl Condition {
+    fn matches(&self, msg: &serenity::Message) -> bool {
+        match self {
+            Condition::User(user) => msg.author.id == *user,
+            Condition::Bot => msg.author.bot,
+            Condition::Link => LINK_REGEX.is_match(&msg.content),
+            Condition::File => !msg.attachments.is_empty(),
+            Condition::Embed => !msg.embeds.is_empty(),
+            Condition::Mention => !msg.mention_roles.is_empty() || !msg.mentions.is_empty(),
+            Condition::Reaction => !msg.reactions.is_empty(),
+            Condition::Contains(text, false) => msg.content.contains(text.as_str()),
+            Condition::Contains(text, true) => msg.content.to_lowercase().contains(text.as_str()),
+            Condition::StartsWith(text, false) => msg.content.starts_with(text.as_str()),
+            Condition::StartsWith(text, true) => msg.content.to_lowercase().starts_with(text.as_str()),
+            Condition::EndsWith(text, false) => msg.content.ends_with(text.as_str()),
+            Condition::EndsWith(text, true) => msg.content.to_lowercase().ends_with(text.as_str()),
+            Condition::Regex(regex) => regex.is_match(&msg.content),
+            Condition::After(id) => msg.id > *id,
+            Condition::Before(id) => msg.id < *id,
+            Condition::Since(timestamp) => msg.timestamp.unix_timestamp() >= *timestamp
+        }
+    }
+}
+
+/// Build a text condition, lowercasing the text up front when matching case-insensitively
+pub fn text_condition(kind: fn(String, bool) -> Condition, text: &str, ignore_case: bool) -> Condition {
+    if ignore_case {
+        kind(text.to_lowercase(), true)
+    } else {
+        kind(text.to_string(), false)
+    }
+}
+
+/// A set of conditions a message has to satisfy to be purged - `user` conditions match if any
+/// of them do, every other condition has to match
+#[derive(Default)]
+pub struct Filter {
+    terms: Vec<(bool, Condition)>
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require messages to match `condition`
+    pub fn with(mut self, condition: Condition) -> Self {
+        self.terms.push((false, condition));
+        self
+    }
+
+    /// Require messages to not match `condition`
+    pub fn without(mut self, condition: Condition) -> Self {
+        self.terms.push((true, condition));
+        self
+    }
+
+    pub fn matches(&self, msg: &serenity::Message) -> bool {
+        let is_user = |negated: &bool, condition: &Condition| !negated && matches!(condition, Condition::User(_));
+        let mut users = self.terms.iter().filter(|(negated, condition)| is_user(negated, condition)).peekable();
+        let user_matches = users.peek().is_none() || users.any(|(_, condition)| condition.matches(msg));
+        user_matches && self
+            .terms
+            .iter()
+            .filter(|(negated, condition)| !is_user(negated, condition))
+            .all(|(negated, condition)| condition.matches(msg) != *negated)
+    }
+}
+
+/// Split an expression on whitespace, keeping "quoted phrases" together
+fn tokenize(expression: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in expression.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c)
+        }
+    }
+    if quoted {
+        return Err(String::from("Unclosed quote in filter"));
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Extract a snowflake from a mention, a message link or a raw ID
+fn parse_snowflake(value: &str) -> Option<u64> {
+    value
+        .rsplit('/')
+        .next()?
+        .trim_start_matches("<@")
+        .trim_start_matches('!')
+        .trim_end_matches('>')
+        .parse()
+        .ok()
+}
+
+fn parse_condition(key: &str, value: Option<&str>) -> Result<Condition, String> {
+    let value = value.filter(|value| !value.is_empty());
+    let require = || value.ok_or_else(|| format!("`{}` needs a value", key));
+    match key {
+        "bot" => Ok(Condition::Bot),
+        "user" => parse_snowflake(require()?)
+            .map(|id| Condition::User(serenity::UserId::new(id)))
+            .ok_or_else(|| format!("Invalid user `{}`", value.unwrap())),
+        "has" => match require()? {
+            "link" => Ok(Condition::Link),
+            "file" => Ok(Condition::File),
+            "embed" => Ok(Condition::Embed),
+            "mention" => Ok(Condition::Mention),
+            "reaction" => Ok(Condition::Reaction),
+            other => Err(format!("Unknown `has:` value `{}` - expected link, file, embed, mention or reaction", other))
+        },
+        "contains" => Ok(Condition::Contains(require()?.to_string(), false)),
+        "startswith" => Ok(Condition::StartsWith(require()?.to_string(), false)),
+        "endswith" => Ok(Condition::EndsWith(require()?.to_string(), false)),
+        "regex" => regex::RegexBuilder::new(require()?)
+            .size_limit(1 << 20)
+            .build()
+            .map(Condition::Regex)
+            .map_err(|e| format!("Invalid regular expression `{}`: {}", value.unwrap(), e)),
+        "after" | "before" => {
+            let id = parse_snowflake(require()?)
+                .map(serenity::MessageId::new)
+                .ok_or_else(|| format!("Invalid message `{}`", value.unwrap()))?;
+            Ok(if key == "after" { Condition::After(id) } else { Condition::Before(id) })
+        }
+        "within" => parse_duration(require()?)
+            .map(|duration| Condition::Since(serenity::Timestamp::now().unix_timestamp() - duration.as_secs() as i64))
+            .map_err(|_| format!("Invalid duration `{}`", value.unwrap())),
+        other => Err(format!("Unknown filter `{}`", other))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    /// Parse an expression like `user:@x has:link -bot within:1h`
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let mut filter = Filter::new();
+        for token in tokenize(expression)? {
+            let (negated, term) = match token.strip_prefix('-') {
+                Some(term) => (true, term),
+                None => (false, token.as_str())
+            };
+            let (key, value) = match term.split_once(':') {
+                Some((key, value)) => (key, Some(value)),
+                None => (term, None)
+            };
+            let condition = parse_condition(&key.to_lowercase(), value)?;
+            filter = if negated { filter.without(condition) } else { filter.with(condition) };
+        }
+        if filter.terms.is_empty() {
+            return Err(String::from("Empty filter"));
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(author: u64, content: &str) -> serenity::Message {
+        let mut msg = serenity::Message::default();
+        msg.author.id = serenity::UserId::new(author);
+        msg.content = content.to_string();
+        msg
+    }
+
+    fn parse_err(expression: &str) -> String {
+        match expression.parse::<Filter>() {
+            Ok(_) => panic!("`{}` parsed", expression),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn tokenize_keeps_quoted_phrases_together() {
+        assert_eq!(
+            tokenize(r#"contains:"free  nitro" -bot   has:link"#).unwrap(),
+            vec!["contains:free  nitro", "-bot", "has:link"]
+        );
+        assert_eq!(tokenize("  ").unwrap(), Vec::<String>::new());
+        assert_eq!(tokenize(r#"contains:"oops"#).unwrap_err(), "Unclosed quote in filter");
+    }
+
+    #[test]
+    fn parse_snowflake_accepts_ids_mentions_and_links() {
+        assert_eq!(parse_snowflake("123456789"), Some(123456789));
+        assert_eq!(parse_snowflake("<@123456789>"), Some(123456789));
+        assert_eq!(parse_snowflake("<@!123456789>"), Some(123456789));
+        assert_eq!(parse_snowflake("https://discord.com/channels/1/2/123456789"), Some(123456789));
+        assert_eq!(parse_snowflake("someone"), None);
+    }
+
+    #[test]
+    fn user_terms_match_any_of_the_users() {
+        let filter: Filter = "user:1 user:<@2>".parse().unwrap();
+        assert!(filter.matches(&message(1, "hi")));
+        assert!(filter.matches(&message(2, "hi")));
+        assert!(!filter.matches(&message(3, "hi")));
+    }
+
+    #[test]
+    fn other_terms_must_all_match() {
+        let filter: Filter = r#"user:1 user:2 has:link contains:"free nitro""#.parse().unwrap();
+        assert!(filter.matches(&message(2, "free nitro at https://example.com")));
+        assert!(!filter.matches(&message(2, "free nitro")));
+        assert!(!filter.matches(&message(2, "https://example.com")));
+        assert!(!filter.matches(&message(3, "free nitro at https://example.com")));
+    }
+
+    #[test]
+    fn negated_terms_exclude_matches() {
+        let filter: Filter = "-user:1 -has:link".parse().unwrap();
+        assert!(filter.matches(&message(2, "hello")));
+        assert!(!filter.matches(&message(1, "hello")));
+        assert!(!filter.matches(&message(2, "http://example.com")));
+
+        let mut msg = message(2, "hello");
+        msg.author.bot = true;
+        assert!(!"-bot".parse::<Filter>().unwrap().matches(&msg));
+        assert!("BOT".parse::<Filter>().unwrap().matches(&msg));
+    }
+
+    #[test]
+    fn parse_errors_explain_the_problem() {
+        assert_eq!(parse_err(""), "Empty filter");
+        assert_eq!(parse_err("contains:"), "`contains` needs a value");
+        assert_eq!(parse_err("user:someone"), "Invalid user `someone`");
+        assert_eq!(parse_err("after:abc"), "Invalid message `abc`");
+        assert_eq!(parse_err("within:soon"), "Invalid duration `soon`");
+        assert_eq!(parse_err("colour:red"), "Unknown filter `colour`");
+        assert_eq!(
+            parse_err("has:sticker"),
+            "Unknown `has:` value `sticker` - expected link, file, embed, mention or reaction"
+        );
+        assert!(parse_err("regex:(").starts_with("Invalid regular expression `(`"));
+    }
+}
@@ -0,0 +1,87 @@
+use poise::serenity_prelude as serenity;
+
+/// Most characters of a message shown in a dry run preview
+const SNIPPET_LENGTH: usize = 60;
+/// Most messages listed in a dry run preview, the rest are only in the attached transcript
+const PREVIEW_LINES: usize = 15;
+
+/// Shorten a message to a single line that fits in a preview
+fn snippet(content: &str) -> String {
+    if content.is_empty() {
+        return String::from("*no text*");
+    }
+    let content = content.replace('`', "'").replace('\n', " ");
+    if content.chars().count() > SNIPPET_LENGTH {
+        format!("`{}…`", content.chars().take(SNIPPET_LENGTH).collect::<String>())
+    } else {
+        format!("`{}`", content)
+    }
+}
+
+/// List the author, timestamp and a snippet of each of `messages` below `header`
+pub fn preview(header: &str, messages: &[serenity::Message]) -> String {
+    let mut preview = format!("Dry run - {}:\n", header);
+    for msg in messages.iter().take(PREVIEW_LINES) {
+        preview.push_str(&format!(
+            "<t:{}:f> **{}**: {}\n",
+            msg.timestamp.unix_timestamp(),
+            msg.author.name,
+            snippet(&msg.content)
+        ));
+    }
+    if messages.len() > PREVIEW_LINES {
+        preview.push_str(&format!("...and {} more, see the attached transcript", messages.len() - PREVIEW_LINES));
+    }
+    preview
+}
+
+/// Most bytes of transcript put in one file, well below Discord's upload limit
+const MAX_FILE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Render `messages` as plain text transcript files, oldest first per channel, including attachment URLs -
+/// split into several files once it grows past `MAX_FILE_SIZE`
+pub fn attachments(moderator: &serenity::User, messages: &[serenity::Message]) -> Vec<serenity::CreateAttachment> {
+    let now = serenity::Timestamp::now();
+    let mut parts = vec![String::new()];
+    let mut channel = None;
+    for msg in messages.iter().rev() {
+        let mut entry = String::new();
+        if channel != Some(msg.channel_id) {
+            channel = Some(msg.channel_id);
+            entry.push_str(&format!("\n--- Channel {} ---\n", msg.channel_id));
+        }
+        entry.push_str(&format!("[{}] {} ({}): {}\n", msg.timestamp, msg.author.name, msg.author.id, msg.content));
+        for attachment in &msg.attachments {
+            entry.push_str(&format!("    Attachment: {}\n", attachment.url));
+        }
+        if !msg.embeds.is_empty() {
+            entry.push_str(&format!("    {} embed(s)\n", msg.embeds.len()));
+        }
+        let part = parts.last_mut().unwrap();
+        if !part.is_empty() && part.len() + entry.len() > MAX_FILE_SIZE {
+            parts.push(format!("\n--- Channel {} (continued) ---\n", msg.channel_id));
+        }
+        parts.last_mut().unwrap().push_str(&entry);
+    }
+    let total = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(idx, part)| {
+            let mut header = format!(
+                "Purge of {} messages by {} ({}) at {}",
+                messages.len(),
+                moderator.name,
+                moderator.id,
+                now
+            );
+            let name = if total == 1 {
+                format!("purge-{}.txt", now.unix_timestamp())
+            } else {
+                header.push_str(&format!(" - part {} of {}", idx + 1, total));
+                format!("purge-{}-{}.txt", now.unix_timestamp(), idx + 1)
+            };
+            serenity::CreateAttachment::bytes(format!("{}\n{}", header, part).into_bytes(), name)
+        })
+        .collect()
+}
@@ -2,9 +2,11 @@ use humantime::{format_duration, parse_rfc3339};
 use poise;
 use poise::serenity_prelude::CacheHttp;
 use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
 use std::time::Duration;
 use std::vec;
 
+use crate::commands::moderation::modlog;
 use crate::Context;
 use crate::Error;
 
@@ -111,6 +113,12 @@ pub async fn add(
         .await?;
     } else {
         member.add_role(ctx.http(), role.id).await?;
+        modlog::post(
+            ctx,
+            modlog::LogEntry::new("Role Add")
+                .target(format!("{} ({})", user.mention(), user.id))
+                .details(format!("Role: {}", role.mention()))
+        ).await?;
         ctx.say(format!("Successfully added role **{}** to **{}**",role.name, user.name))
         .await?;
     }
@@ -142,6 +150,12 @@ pub async fn remove(
         .await?
     {
         member.remove_role(ctx.http(), role.id).await?;
+        modlog::post(
+            ctx,
+            modlog::LogEntry::new("Role Remove")
+                .target(format!("{} ({})", user.mention(), user.id))
+                .details(format!("Role: {}", role.mention()))
+        ).await?;
         ctx.say(format!("Successfully removed role **{}** from **{}**", role.name, user.name))
         .await?;
     } else {
@@ -279,6 +293,15 @@ pub async fn rall(
                 &members_with_role.len() - fail as usize,
                 &fail))
         ).await?;
+        modlog::post(
+            ctx,
+            modlog::LogEntry::new("Role Remove All")
+                .target(role.mention().to_string())
+                .details(format!(
+                    "Removed from {} members, failed for {}",
+                    &members_with_role.len() - fail as usize,
+                    &fail))
+        ).await?;
     }
     else {
         msg.edit(
@@ -0,0 +1,249 @@
+use std::time::Duration;
+use humantime::{format_duration, parse_duration};
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+
+use crate::commands::moderation::channel::parse_slowmode;
+use crate::commands::moderation::{modlog, PACE};
+use crate::Error;
+use crate::Context;
+
+/// The thread to act on - the given one or the current channel. Replies and returns `None` if it's not a thread
+async fn resolve_thread(ctx: Context<'_>, thread: Option<serenity::GuildChannel>) -> Result<Option<serenity::GuildChannel>, Error> {
+    let thread = match thread {
+        Some(thread) => thread,
+        None => ctx.guild_channel().await.unwrap()
+    };
+    if thread.thread_metadata.is_none() {
+        ctx.say(format!("{} is not a thread", thread.mention())).await?;
+        return Ok(None);
+    }
+    Ok(Some(thread))
+}
+
+/// When a thread last saw activity - its last message, or its creation if it has none
+fn last_activity(thread: &serenity::GuildChannel) -> i64 {
+    thread
+        .last_message_id
+        .map(|msg| msg.created_at())
+        .or_else(|| thread.thread_metadata.and_then(|metadata| metadata.create_timestamp))
+        .unwrap_or_else(|| thread.id.created_at())
+        .unix_timestamp()
+}
+
+/// Apply `edit` to a thread, then confirm and log it
+async fn edit_thread(
+    ctx: Context<'_>,
+    action: &str,
+    thread: Option<serenity::GuildChannel>,
+    edit: serenity::EditThread<'_>,
+    details: Option<String>,
+    reason: Option<String>
+) -> Result<(), Error> {
+    let Some(mut thread) = resolve_thread(ctx, thread).await? else {
+        return Ok(());
+    };
+    let edit = match &reason {
+        Some(reason) => edit.audit_log_reason(reason),
+        None => edit
+    };
+    thread.edit_thread(ctx.http(), edit).await?;
+    ctx.say(format!("{} {}", action, thread.mention())).await?;
+
+    let mut entry = modlog::LogEntry::new(format!("Thread {}", action)).channel(thread.id);
+    if let Some(details) = details {
+        entry = entry.details(details);
+    }
+    if let Some(reason) = reason {
+        entry = entry.reason(reason);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
+
+/// Base command for thread moderation
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    subcommands("lock", "unlock", "archive", "rename", "slowmode", "purge_inactive"),
+    guild_only = true
+    )
+]
+pub async fn thread(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Lock a thread so only moderators can send messages or unarchive it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn lock(
+    ctx: Context<'_>,
+    #[description = "Thread to lock - defaults to current thread"] thread: Option<serenity::GuildChannel>,
+    #[description = "Reason for the lock"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    edit_thread(ctx, "Locked", thread, serenity::EditThread::new().locked(true), None, reason).await
+}
+
+
+/// Unlock a locked thread
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn unlock(
+    ctx: Context<'_>,
+    #[description = "Thread to unlock - defaults to current thread"] thread: Option<serenity::GuildChannel>,
+    #[description = "Reason for the unlock"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    edit_thread(ctx, "Unlocked", thread, serenity::EditThread::new().locked(false), None, reason).await
+}
+
+
+/// Archive a thread
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn archive(
+    ctx: Context<'_>,
+    #[description = "Thread to archive - defaults to current thread"] thread: Option<serenity::GuildChannel>,
+    #[description = "Reason for archiving"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    edit_thread(ctx, "Archived", thread, serenity::EditThread::new().archived(true), None, reason).await
+}
+
+
+/// Rename a thread
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn rename(
+    ctx: Context<'_>,
+    #[description = "New name of the thread"] name: String,
+    #[description = "Thread to rename - defaults to current thread"] thread: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    if name.is_empty() || name.chars().count() > 100 {
+        ctx.say("Thread names must be between 1 and 100 characters").await?;
+        return Ok(());
+    }
+    let details = format!("New name: {}", name);
+    edit_thread(ctx, "Renamed", thread, serenity::EditThread::new().name(name), Some(details), None).await
+}
+
+
+/// Set the slowmode of a thread
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn slowmode(
+    ctx: Context<'_>,
+    #[description = "The slowmode to set"] time: String,
+    #[description = "Thread for which the slowmode is to be set - defaults to current thread"] thread: Option<serenity::GuildChannel>
+) -> Result<(), Error> {
+    let seconds = match parse_slowmode(&time) {
+        Ok(seconds) => seconds,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+    let details = format!("Slowmode: {}", format_duration(Duration::from_secs(seconds as u64)));
+    edit_thread(ctx, "Slowmode Set", thread, serenity::EditThread::new().rate_limit_per_user(seconds), Some(details), None).await
+}
+
+
+/// Archive every thread of a forum or channel with no activity for the given duration
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "purge-inactive",
+    required_permissions = "MANAGE_THREADS",
+    guild_only = true
+    )
+]
+pub async fn purge_inactive(
+    ctx: Context<'_>,
+    #[description = "Forum or channel to clean up"] channel: serenity::GuildChannel,
+    #[description = "Archive threads inactive for longer than this, e.g. 7d"] inactive: String,
+    #[description = "List the threads that would be archived without archiving them"] dry_run: Option<bool>
+) -> Result<(), Error> {
+    let Ok(inactive) = parse_duration(&inactive) else {
+        ctx.say(format!("Invalid duration `{}`", inactive)).await?;
+        return Ok(());
+    };
+    let cutoff = serenity::Timestamp::now().unix_timestamp() - inactive.as_secs() as i64;
+    let mut threads: Vec<serenity::GuildChannel> = ctx
+        .guild_id()
+        .unwrap()
+        .get_active_threads(ctx.http())
+        .await?
+        .threads
+        .into_iter()
+        .filter(|thread| thread.parent_id == Some(channel.id))
+        .filter(|thread| !thread.flags.contains(serenity::ChannelFlags::PINNED))
+        .filter(|thread| last_activity(thread) < cutoff)
+        .collect();
+    threads.sort_by_key(last_activity);
+    if threads.is_empty() {
+        ctx.say(format!("No threads in {} inactive for {}", channel.mention(), format_duration(inactive))).await?;
+        return Ok(());
+    }
+
+    if dry_run.unwrap_or_default() {
+        let mut preview = format!("Dry run - would archive {} threads:\n", threads.len());
+        for thread in threads.iter().take(20) {
+            preview.push_str(&format!("{} - last active <t:{}:R>\n", thread.mention(), last_activity(thread)));
+        }
+        if threads.len() > 20 {
+            preview.push_str(&format!("...and {} more", threads.len() - 20));
+        }
+        ctx.say(preview).await?;
+        return Ok(());
+    }
+
+    let reply = ctx.say(format!("Archiving {} threads", threads.len())).await?;
+    let mut failed = 0;
+    for (idx, thread) in threads.iter().enumerate() {
+        if idx != 0 {
+            tokio::time::sleep(PACE).await;
+        }
+        if let Err(e) = thread.id.edit_thread(ctx.http(), serenity::EditThread::new().archived(true)).await {
+            tracing::warn!("Failed to archive inactive thread {}: {}", thread.id, e);
+            failed += 1;
+        }
+    }
+    let mut summary = format!("Archived {} threads", threads.len() - failed);
+    if failed != 0 {
+        summary.push_str(&format!(", failed to archive {}", failed));
+    }
+    reply.edit(ctx, poise::CreateReply::default().content(&summary)).await?;
+    modlog::post(
+        ctx,
+        modlog::LogEntry::new("Thread Purge Inactive")
+            .channel(channel.id)
+            .details(format!("{} - inactive for over {}", summary, format_duration(inactive)))
+    ).await?;
+    Ok(())
+}
@@ -1,12 +1,112 @@
-use humantime::parse_duration;
+use std::time::Duration;
+use humantime::{format_duration, parse_duration};
 use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::Mentionable;
+use poise::ChoiceParameter;
 // use poise::CreateReply;
 
 
+use crate::commands::moderation::case;
+use crate::config::MAX_TIMEOUT;
 use crate::Error;
 use crate::Context;
 
+/// Action taken automatically once a member reaches a configured number of warnings
+#[derive(poise::ChoiceParameter)]
+pub enum EscalationAction {
+    Timeout,
+    Kick,
+    Ban
+}
+
+/// Timeout a member for the given duration and record the case
+pub async fn timeout_member(
+    ctx: Context<'_>,
+    member: &mut serenity::Member,
+    duration: Duration,
+    reason: Option<&str>
+) -> Result<(), Error> {
+    let timeout_ts = serenity::Timestamp::from_millis(
+        duration.as_millis() as i64 + serenity::Timestamp::now().timestamp_millis()
+    ).unwrap();
+    member.disable_communication_until_datetime(ctx.http(), timeout_ts).await?;
+    case::record(ctx, member.user.id, "Timeout", reason, Some(duration)).await?;
+    Ok(())
+}
+
+/// Kick a member and record the case
+pub async fn kick_member(ctx: Context<'_>, user: serenity::UserId, reason: &str) -> Result<(), Error> {
+    ctx.http()
+        .kick_member(
+            ctx.guild_id().unwrap(),
+            user,
+            Some(reason)
+            ).await?;
+    case::record(ctx, user, "Kick", Some(reason), None).await?;
+    Ok(())
+}
+
+/// Drop a pending tempban expiry so the scheduler doesn't lift a ban that has since been replaced or lifted
+pub async fn forget_tempban(ctx: Context<'_>, user: serenity::UserId) -> Result<(), Error> {
+    sqlx::query("DELETE FROM tempbans WHERE guild_id = $1 AND user_id = $2")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(user.get() as i64)
+        .execute(&ctx.data().db)
+        .await?;
+    Ok(())
+}
+
+/// Ban a member, deleting their messages from the last `delete_days` days, and record the case
+pub async fn ban_member(ctx: Context<'_>, user: serenity::UserId, delete_days: u8, reason: &str) -> Result<(), Error> {
+    ctx.http()
+        .ban_user(
+            ctx.guild_id().unwrap(),
+            user,
+            delete_days,
+            Some(reason)
+            ).await?;
+    forget_tempban(ctx, user).await?;
+    case::record(ctx, user, "Ban", Some(reason), None).await?;
+    Ok(())
+}
+
+/// Run the escalation of the highest threshold the given warning count has reached, if any, and describe what
+/// was done - so members already past a threshold when it's added still get escalated on their next warning
+async fn escalate(ctx: Context<'_>, user: &serenity::User, count: i64) -> Result<Option<String>, Error> {
+    let threshold: Option<(String, Option<i64>)> = sqlx::query_as(
+        "SELECT action, duration FROM warn_thresholds WHERE guild_id = $1 AND warn_count <= $2
+        ORDER BY warn_count DESC LIMIT 1"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .bind(count as i32)
+    .fetch_optional(&ctx.data().db)
+    .await?;
+    let Some((action, duration)) = threshold else {
+        return Ok(None);
+    };
+    let reason = format!("Automatic {} after reaching {} warnings", action.to_lowercase(), count);
+    match EscalationAction::from_name(&action) {
+        Some(EscalationAction::Timeout) => {
+            let duration = match duration {
+                Some(duration) => Duration::from_secs(duration as u64),
+                None => ctx.data().config.get(&ctx.data().db, ctx.guild_id().unwrap()).await?.default_timeout()
+            };
+            let mut member = ctx.http().get_member(ctx.guild_id().unwrap(), user.id).await?;
+            timeout_member(ctx, &mut member, duration, Some(&reason)).await?;
+            Ok(Some(format!("{} was timed out for {}", user.name, format_duration(duration))))
+        }
+        Some(EscalationAction::Kick) => {
+            kick_member(ctx, user.id, &reason).await?;
+            Ok(Some(format!("{} was kicked", user.name)))
+        }
+        Some(EscalationAction::Ban) => {
+            ban_member(ctx, user.id, 0, &reason).await?;
+            Ok(Some(format!("{} was banned", user.name)))
+        }
+        None => Ok(None)
+    }
+}
+
 /// Timeout a User
 #[poise::command(
     slash_command,
@@ -18,18 +118,27 @@ use crate::Context;
 pub async fn timeout(
     ctx: Context<'_>,
     #[description = "User to timeout"] user: serenity::User,
-    #[description = "Time to timeout the user for"] time: Option<String>
+    #[description = "Time to timeout the user for - defaults to the server default"] time: Option<String>
 ) -> Result<(), Error> {
-    let time = time.unwrap_or_else(|| "1h".to_string());
+    let time = match time {
+        Some(time) => time,
+        None => format_duration(ctx.data().config.get(&ctx.data().db, ctx.guild_id().unwrap()).await?.default_timeout()).to_string()
+    };
+    let duration = match parse_duration(&time) {
+        Ok(duration) if duration <= MAX_TIMEOUT => duration,
+        Ok(_) => {
+            ctx.say("Timeouts can be at most 28 days long").await?;
+            return Ok(());
+        }
+        Err(_) => {
+            ctx.say(format!("Invalid duration `{}`", time)).await?;
+            return Ok(());
+        }
+    };
     let mut member = ctx.http().get_member(ctx.guild_id().unwrap(), user.id).await?;
-    let timeout_ts = serenity::Timestamp::from_millis(
-        parse_duration(&time)
-        .unwrap_or_default()
-        .as_millis() as i64 + serenity::Timestamp::now().timestamp_millis()
-    ).unwrap();
 
     if member.communication_disabled_until.unwrap_or_else(|| serenity::Timestamp::from_millis(0).unwrap()) <= serenity::Timestamp::now() {
-        let _ = &member.disable_communication_until_datetime(ctx.http(), timeout_ts).await?;
+        timeout_member(ctx, &mut member, duration, None).await?;
         ctx.say(format!("{} was timed out for {}", user.name, time)).await?;
     }
     else {
@@ -57,6 +166,7 @@ pub async fn untimeout(
         .unwrap_or_else(|| serenity::Timestamp::from_millis(0).unwrap())
         >= serenity::Timestamp::now() {
             member.enable_communication(ctx.http()).await?;
+            case::record(ctx, user.id, "Untimeout", None, None).await?;
             ctx.say(format!("Successfully removed timeout from {}", user.name)).await?;
     }
     else {
@@ -77,17 +187,106 @@ pub async fn untimeout(
 pub async fn ban(
     ctx: Context<'_>,
     #[description = "Member to ban"] user: serenity::User,
-    #[description = "Reason"] mut reason: Option<String>
+    #[description = "Reason"] reason: Option<String>,
+    #[description = "Days of message history to delete (0-7) - defaults to 0"]
+    #[min = 0]
+    #[max = 7]
+    delete_days: Option<u8>
+) -> Result<(), Error> {
+    let reason = reason.unwrap_or_else(|| format!("Requested by {}", &ctx.author().name));
+    let delete_days = delete_days.unwrap_or_default();
+    if delete_days > 7 {
+        ctx.say("Messages can only be deleted from up to 7 days back").await?;
+        return Ok(());
+    }
+    ban_member(ctx, user.id, delete_days, &reason).await?;
+    ctx.say(format!("Successfully banned {}", user.mention())).await?;
+    Ok(())
+}
+
+
+/// Softban a member - ban and immediately unban them to delete their recent messages
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "BAN_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn softban(
+    ctx: Context<'_>,
+    #[description = "Member to softban"] user: serenity::User,
+    #[description = "Reason"] reason: Option<String>,
+    #[description = "Days of message history to delete (1-7) - defaults to 7"]
+    #[min = 1]
+    #[max = 7]
+    delete_days: Option<u8>
 ) -> Result<(), Error> {
-    reason = Some(reason.unwrap_or_else(|| format!("Requested by {}", &ctx.author().name)));
+    let reason = reason.unwrap_or_else(|| format!("Requested by {}", &ctx.author().name));
+    let delete_days = delete_days.unwrap_or(7);
+    if !(1..=7).contains(&delete_days) {
+        ctx.say("Messages can only be deleted from 1 to 7 days back").await?;
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().unwrap();
     ctx.http()
         .ban_user(
-            ctx.guild_id().unwrap(),
+            guild_id,
+            user.id,
+            delete_days,
+            Some(&reason)
+            ).await?;
+    ctx.http()
+        .remove_ban(
+            guild_id,
+            user.id,
+            Some(&reason)
+            ).await?;
+    forget_tempban(ctx, user.id).await?;
+    case::record(ctx, user.id, "Softban", Some(&reason), None).await?;
+    ctx.say(format!("Successfully softbanned {} and deleted {} day(s) of their messages", user.mention(), delete_days)).await?;
+    Ok(())
+}
+
+
+/// Temporarily ban a member - they are unbanned automatically once the duration is over
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "BAN_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn tempban(
+    ctx: Context<'_>,
+    #[description = "Member to ban"] user: serenity::User,
+    #[description = "Time to ban the user for"] time: String,
+    #[description = "Reason"] reason: Option<String>
+) -> Result<(), Error> {
+    let Ok(duration) = parse_duration(&time) else {
+        ctx.say(format!("Invalid duration `{}`", time)).await?;
+        return Ok(());
+    };
+    let reason = reason.unwrap_or_else(|| format!("Requested by {}", &ctx.author().name));
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.http()
+        .ban_user(
+            guild_id,
             user.id,
             0,
-            reason.as_deref()
+            Some(&reason)
             ).await?;
-    ctx.say(format!("Successfully banned {}", user.mention())).await?;
+    sqlx::query(
+        "INSERT INTO tempbans (guild_id, user_id, expires_at) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, user_id) DO UPDATE SET expires_at = EXCLUDED.expires_at"
+    )
+    .bind(guild_id.get() as i64)
+    .bind(user.id.get() as i64)
+    .bind(serenity::Timestamp::now().unix_timestamp() + duration.as_secs() as i64)
+    .execute(&ctx.data().db)
+    .await?;
+    case::record(ctx, user.id, "Tempban", Some(&reason), Some(duration)).await?;
+    ctx.say(format!("Successfully banned {} for {}", user.mention(), format_duration(duration))).await?;
     Ok(())
 }
 
@@ -112,6 +311,8 @@ pub async fn unban(
             user,
             reason.as_deref()
             ).await?;
+    forget_tempban(ctx, user).await?;
+    case::record(ctx, user, "Unban", reason.as_deref(), None).await?;
     ctx.say(format!("Successfully unbanned {}", user.mention())).await?;
     Ok(())
 }
@@ -128,15 +329,274 @@ pub async fn unban(
 pub async fn kick(
     ctx: Context<'_>,
     #[description = "User to kick"] user: serenity::User,
-    #[description = "Reason"] mut reason: Option<String>
+    #[description = "Reason"] reason: Option<String>
 ) -> Result<(), Error> {
-    reason = Some(reason.unwrap_or_else(|| format!("Requested by {}", ctx.author().name)));
-    ctx.http()
-        .kick_member(
-            ctx.guild_id().unwrap(),
-            user.id,
-            reason.as_deref()
-            ).await?;
+    let reason = reason.unwrap_or_else(|| format!("Requested by {}", ctx.author().name));
+    kick_member(ctx, user.id, &reason).await?;
     ctx.say(format!("Successfully kicked {}", user.mention())).await?;
     Ok(())
 }
+
+
+/// Warn a member - runs the guild's escalation policy if a threshold is reached
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "User to warn"] user: serenity::User,
+    #[description = "Reason"] #[rest] reason: String
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get() as i64;
+    sqlx::query(
+        "INSERT INTO warnings (guild_id, user_id, moderator_id, reason, created_at) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(guild_id)
+    .bind(user.id.get() as i64)
+    .bind(ctx.author().id.get() as i64)
+    .bind(&reason)
+    .bind(serenity::Timestamp::now().unix_timestamp())
+    .execute(&ctx.data().db)
+    .await?;
+    case::record(ctx, user.id, "Warn", Some(&reason), None).await?;
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM warnings WHERE guild_id = $1 AND user_id = $2")
+        .bind(guild_id)
+        .bind(user.id.get() as i64)
+        .fetch_one(&ctx.data().db)
+        .await?;
+    let mut response = format!("Warned {} - they now have {} warning(s)", user.mention(), count);
+    match escalate(ctx, &user, count).await {
+        Ok(Some(escalation)) => response.push_str(&format!("\n{}", escalation)),
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!("Failed to escalate the warning of {}: {}", user.id, e);
+            response.push_str(&format!("\nThe warning was saved, but the escalation failed: {}", e));
+        }
+    }
+    ctx.say(response).await?;
+    Ok(())
+}
+
+
+/// List the warnings of a member
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn warnings(
+    ctx: Context<'_>,
+    #[description = "User whose warnings to show"] user: serenity::User
+) -> Result<(), Error> {
+    let warnings: Vec<(i64, i64, String, i64)> = sqlx::query_as(
+        "SELECT id, moderator_id, reason, created_at FROM warnings WHERE guild_id = $1 AND user_id = $2 ORDER BY id DESC"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .bind(user.id.get() as i64)
+    .fetch_all(&ctx.data().db)
+    .await?;
+    if warnings.is_empty() {
+        ctx.say(format!("**{}** has no warnings", user.name)).await?;
+        return Ok(());
+    }
+    let pages: Vec<String> = warnings
+        .chunks(10)
+        .map(|chunk| {
+            let body = chunk
+                .iter()
+                .map(|(id, moderator_id, reason, created_at)| {
+                    format!("**#{}** | <t:{}:f> | by <@{}>\n{}", id, created_at, moderator_id, reason)
+                })
+                .collect::<Vec<String>>()
+                .join("\n\n");
+            format!("Warnings for **{}** ({} total)\n\n{}", user.name, warnings.len(), body)
+        })
+        .collect();
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+
+/// Delete a warning by its ID
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn delwarn(
+    ctx: Context<'_>,
+    #[description = "ID of the warning to delete"] id: i64
+) -> Result<(), Error> {
+    let deleted = sqlx::query("DELETE FROM warnings WHERE guild_id = $1 AND id = $2")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(id)
+        .execute(&ctx.data().db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        ctx.say(format!("Warning #{} not found", id)).await?;
+    }
+    else {
+        ctx.say(format!("Deleted warning #{}", id)).await?;
+    }
+    Ok(())
+}
+
+
+/// Clear all warnings of a member
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MODERATE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn clearwarns(
+    ctx: Context<'_>,
+    #[description = "User whose warnings to clear"] user: serenity::User
+) -> Result<(), Error> {
+    let deleted = sqlx::query("DELETE FROM warnings WHERE guild_id = $1 AND user_id = $2")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(user.id.get() as i64)
+        .execute(&ctx.data().db)
+        .await?
+        .rows_affected();
+    ctx.say(format!("Cleared {} warning(s) from **{}**", deleted, user.name)).await?;
+    Ok(())
+}
+
+
+/// Base command for the warning escalation policy
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("set", "unset", "list"),
+    guild_only = true
+    )
+]
+pub async fn warnpolicy(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Set the action taken when a member reaches a number of warnings
+///
+/// Every warning after that repeats the action of the highest threshold reached
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Number of warnings that triggers the action"] count: u16,
+    #[description = "Action to take"] action: EscalationAction,
+    #[description = "Timeout duration - defaults to the server default"] duration: Option<String>
+) -> Result<(), Error> {
+    if count == 0 {
+        ctx.say("The warning count must be at least 1").await?;
+        return Ok(());
+    }
+    let mut duration_ = None;
+    if let (EscalationAction::Timeout, Some(time)) = (&action, duration) {
+        match parse_duration(&time) {
+            Ok(time) if time <= MAX_TIMEOUT => duration_ = Some(time.as_secs() as i64),
+            Ok(_) => {
+                ctx.say("Timeouts can be at most 28 days long").await?;
+                return Ok(());
+            }
+            Err(_) => {
+                ctx.say(format!("Invalid duration `{}`", time)).await?;
+                return Ok(());
+            }
+        }
+    }
+    sqlx::query(
+        "INSERT INTO warn_thresholds (guild_id, warn_count, action, duration) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, warn_count) DO UPDATE SET action = EXCLUDED.action, duration = EXCLUDED.duration"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .bind(count as i32)
+    .bind(action.name())
+    .bind(duration_)
+    .execute(&ctx.data().db)
+    .await?;
+    ctx.say(format!("Members reaching {} warnings will now receive a {}", count, action.name().to_lowercase())).await?;
+    Ok(())
+}
+
+
+/// Remove the action taken at a number of warnings
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn unset(
+    ctx: Context<'_>,
+    #[description = "Number of warnings to remove the action for"] count: u16
+) -> Result<(), Error> {
+    let deleted = sqlx::query("DELETE FROM warn_thresholds WHERE guild_id = $1 AND warn_count = $2")
+        .bind(ctx.guild_id().unwrap().get() as i64)
+        .bind(count as i32)
+        .execute(&ctx.data().db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        ctx.say(format!("No action set for {} warnings", count)).await?;
+    }
+    else {
+        ctx.say(format!("Removed the action for {} warnings", count)).await?;
+    }
+    Ok(())
+}
+
+
+/// List the warning escalation policy of this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_GUILD",
+    guild_only = true
+    )
+]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let thresholds: Vec<(i32, String, Option<i64>)> = sqlx::query_as(
+        "SELECT warn_count, action, duration FROM warn_thresholds WHERE guild_id = $1 ORDER BY warn_count"
+    )
+    .bind(ctx.guild_id().unwrap().get() as i64)
+    .fetch_all(&ctx.data().db)
+    .await?;
+    if thresholds.is_empty() {
+        ctx.say("No escalation policy set").await?;
+        return Ok(());
+    }
+    let description = thresholds
+        .iter()
+        .map(|(count, action, duration)| match duration {
+            Some(duration) => format!("{} warns → {} {}", count, format_duration(Duration::from_secs(*duration as u64)), action.to_lowercase()),
+            None => format!("{} warns → {}", count, action.to_lowercase())
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let embed = serenity::CreateEmbed::new()
+        .title("Warning escalation policy")
+        .description(description);
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::Mentionable;
+
+use crate::commands::moderation::channel::{set_targets, OverwriteState};
+use crate::commands::moderation::{modlog, PACE};
+use crate::Error;
+use crate::Context;
+
+/// Whether a channel is one members can connect to
+fn is_voice(channel: &serenity::GuildChannel) -> bool {
+    matches!(channel.kind, serenity::ChannelType::Voice | serenity::ChannelType::Stage)
+}
+
+/// The members a voice action applies to - a single connected member, or everyone connected to
+/// `channel` except the moderator - along with a description for the mod-log. Replies and returns
+/// `None` if there is nobody to act on
+async fn voice_targets(
+    ctx: Context<'_>,
+    member: Option<serenity::Member>,
+    channel: Option<serenity::GuildChannel>
+) -> Result<Option<(Vec<serenity::UserId>, String)>, Error> {
+    let error = match (member, channel) {
+        (Some(member), None) => {
+            let connected = ctx
+                .guild()
+                .unwrap()
+                .voice_states
+                .get(&member.user.id)
+                .is_some_and(|state| state.channel_id.is_some());
+            if connected {
+                return Ok(Some((vec![member.user.id], member.user.mention().to_string())));
+            }
+            format!("{} is not in a voice channel", member.user.mention())
+        }
+        (None, Some(channel)) if is_voice(&channel) => {
+            let users: Vec<serenity::UserId> = ctx
+                .guild()
+                .unwrap()
+                .voice_states
+                .values()
+                .filter(|state| state.channel_id == Some(channel.id))
+                .map(|state| state.user_id)
+                .filter(|user| *user != ctx.author().id)
+                .collect();
+            if !users.is_empty() {
+                return Ok(Some((users, format!("Everyone in {}", channel.mention()))));
+            }
+            format!("Nobody else is connected to {}", channel.mention())
+        }
+        (None, Some(channel)) => format!("{} is not a voice channel", channel.mention()),
+        _ => String::from("Give either a member or a voice channel")
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(error)
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+    ).await?;
+    Ok(None)
+}
+
+/// Apply `edit` to every one of `users`, then report and log how many succeeded
+async fn edit_members(
+    ctx: Context<'_>,
+    action: &str,
+    users: Vec<serenity::UserId>,
+    target: String,
+    edit: serenity::EditMember<'_>,
+    details: Option<String>,
+    reason: Option<String>
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let edit = match &reason {
+        Some(reason) => edit.audit_log_reason(reason),
+        None => edit
+    };
+    let mut failed = 0;
+    for (idx, user) in users.iter().enumerate() {
+        if idx != 0 {
+            tokio::time::sleep(PACE).await;
+        }
+        if let Err(e) = guild_id.edit_member(ctx.http(), *user, edit.clone()).await {
+            tracing::warn!("Failed voice action {} on {}: {}", action, user, e);
+            failed += 1;
+        }
+    }
+    let mut summary = format!("{}: {} done", action, users.len() - failed);
+    if failed != 0 {
+        summary.push_str(&format!(", {} failed", failed));
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("{} - {}", target, summary))
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+    ).await?;
+
+    let mut entry = modlog::LogEntry::new(action).target(target).details(match details {
+        Some(details) => format!("{}\n{}", details, summary),
+        None => summary
+    });
+    if let Some(reason) = reason {
+        entry = entry.reason(reason);
+    }
+    modlog::post(ctx, entry).await?;
+    Ok(())
+}
+
+/// The channel to lock - the given one, or the voice channel the moderator is connected to
+async fn lock_channel(ctx: Context<'_>, channel: Option<serenity::GuildChannel>) -> Result<Option<serenity::GuildChannel>, Error> {
+    let channel = match channel {
+        Some(channel) => Some(channel),
+        None => {
+            let guild = ctx.guild().unwrap();
+            guild.voice_states
+                .get(&ctx.author().id)
+                .and_then(|state| state.channel_id)
+                .and_then(|channel| guild.channels.get(&channel))
+                .cloned()
+        }
+    };
+    match channel {
+        Some(channel) if is_voice(&channel) => Ok(Some(channel)),
+        Some(channel) => {
+            ctx.say(format!("{} is not a voice channel", channel.mention())).await?;
+            Ok(None)
+        }
+        None => {
+            ctx.say("Give a voice channel or join one").await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Base command for voice moderation - permissions are checked per subcommand since they share none
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("mute", "deafen", "disconnect", "move_members", "lock", "unlock"),
+    guild_only = true
+    )
+]
+pub async fn voice(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("This is the base command and is not to be run independently").await?;
+    Ok(())
+}
+
+
+/// Server mute a member, or everyone in a voice channel except you
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MUTE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn mute(
+    ctx: Context<'_>,
+    #[description = "Member to mute"] member: Option<serenity::Member>,
+    #[description = "Voice channel to mute everyone in"] channel: Option<serenity::GuildChannel>,
+    #[description = "True to mute, false to unmute - defaults to true"] state: Option<bool>,
+    #[description = "Reason for the mute"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    let Some((users, target)) = voice_targets(ctx, member, channel).await? else {
+        return Ok(());
+    };
+    let state = state.unwrap_or(true);
+    let action = if state { "Voice Mute" } else { "Voice Unmute" };
+    edit_members(ctx, action, users, target, serenity::EditMember::new().mute(state), None, reason).await
+}
+
+
+/// Server deafen a member, or everyone in a voice channel except you
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "DEAFEN_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn deafen(
+    ctx: Context<'_>,
+    #[description = "Member to deafen"] member: Option<serenity::Member>,
+    #[description = "Voice channel to deafen everyone in"] channel: Option<serenity::GuildChannel>,
+    #[description = "True to deafen, false to undeafen - defaults to true"] state: Option<bool>,
+    #[description = "Reason for the deafen"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    let Some((users, target)) = voice_targets(ctx, member, channel).await? else {
+        return Ok(());
+    };
+    let state = state.unwrap_or(true);
+    let action = if state { "Voice Deafen" } else { "Voice Undeafen" };
+    edit_members(ctx, action, users, target, serenity::EditMember::new().deafen(state), None, reason).await
+}
+
+
+/// Disconnect a member, or everyone in a voice channel except you
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MOVE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn disconnect(
+    ctx: Context<'_>,
+    #[description = "Member to disconnect"] member: Option<serenity::Member>,
+    #[description = "Voice channel to disconnect everyone from"] channel: Option<serenity::GuildChannel>,
+    #[description = "Reason for the disconnect"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    let Some((users, target)) = voice_targets(ctx, member, channel).await? else {
+        return Ok(());
+    };
+    edit_members(ctx, "Voice Disconnect", users, target, serenity::EditMember::new().disconnect_member(), None, reason).await
+}
+
+
+/// Move a member, or everyone in a voice channel except you, to another voice channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "move",
+    required_permissions = "MOVE_MEMBERS",
+    guild_only = true
+    )
+]
+pub async fn move_members(
+    ctx: Context<'_>,
+    #[description = "Voice channel to move to"] target: serenity::GuildChannel,
+    #[description = "Member to move"] member: Option<serenity::Member>,
+    #[description = "Voice channel to move everyone from"] channel: Option<serenity::GuildChannel>,
+    #[description = "Reason for the move"] #[rest] reason: Option<String>
+) -> Result<(), Error> {
+    if !is_voice(&target) {
+        ctx.say(format!("{} is not a voice channel", target.mention())).await?;
+        return Ok(());
+    }
+    let Some((users, moved)) = voice_targets(ctx, member, channel).await? else {
+        return Ok(());
+    };
+    let details = format!("Moved to {}", target.mention());
+    edit_members(ctx, "Voice Move", users, moved, serenity::EditMember::new().voice_channel(target.id), Some(details), reason).await
+}
+
+
+/// Stop one or more roles and users from joining a voice channel - defaults to everyone
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn lock(
+    ctx: Context<'_>,
+    #[description = "Voice channel to lock - defaults to the one you're in"] channel: Option<serenity::GuildChannel>,
+    #[description = "Roles/users to lock channel for - defaults to everyone"] targets: Option<String>,
+    #[description = "How long to lock for, e.g. 10m - defaults to until unlocked"] duration: Option<String>
+) -> Result<(), Error> {
+    let Some(channel) = lock_channel(ctx, channel).await? else {
+        return Ok(());
+    };
+    set_targets(ctx, "Voice Lock", Some(channel), targets, serenity::Permissions::CONNECT, OverwriteState::Deny, duration).await
+}
+
+
+/// Let one or more roles and users join a locked voice channel again - defaults to everyone
+#[poise::command(
+    slash_command,
+    prefix_command,
+    required_permissions = "MANAGE_CHANNELS | MANAGE_ROLES",
+    guild_only = true
+    )
+]
+pub async fn unlock(
+    ctx: Context<'_>,
+    #[description = "Voice channel to unlock - defaults to the one you're in"] channel: Option<serenity::GuildChannel>,
+    #[description = "Roles/users to unlock channel for - defaults to everyone"] targets: Option<String>,
+    #[description = "State to set CONNECT to, true for true, false for default - defaults to false"] state: Option<bool>
+) -> Result<(), Error> {
+    let Some(channel) = lock_channel(ctx, channel).await? else {
+        return Ok(());
+    };
+    let state = if state.unwrap_or_default() { OverwriteState::Allow } else { OverwriteState::Inherit };
+    set_targets(ctx, "Voice Unlock", Some(channel), targets, serenity::Permissions::CONNECT, state, None).await
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use poise::serenity_prelude as serenity;
+use sqlx::PgPool;
+
+use crate::Data;
+use crate::Error;
+
+/// Prefix used in DMs and in guilds that haven't set their own
+pub const DEFAULT_PREFIX: &str = "j.";
+/// Timeout applied when none is given and the guild hasn't set its own default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// Longest timeout Discord allows
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+/// Number of messages purged when none is given and the guild hasn't set its own default
+pub const DEFAULT_PURGE: u8 = 100;
+
+/// A configurable per-guild setting
+#[derive(poise::ChoiceParameter, Clone, Copy)]
+pub enum ConfigKey {
+    #[name = "prefix"]
+    Prefix,
+    #[name = "modlog_channel"]
+    ModlogChannel,
+    #[name = "mute_role"]
+    MuteRole,
+    #[name = "default_timeout"]
+    DefaultTimeout,
+    #[name = "default_purge"]
+    DefaultPurge,
+    #[name = "snapshot_interval"]
+    SnapshotInterval
+}
+
+impl ConfigKey {
+    fn column(&self) -> &'static str {
+        match self {
+            ConfigKey::Prefix => "prefix",
+            ConfigKey::ModlogChannel => "modlog_channel",
+            ConfigKey::MuteRole => "mute_role",
+            ConfigKey::DefaultTimeout => "default_timeout",
+            ConfigKey::DefaultPurge => "default_purge",
+            ConfigKey::SnapshotInterval => "snapshot_interval"
+        }
+    }
+}
+
+/// A value to store for a [`ConfigKey`]
+pub enum ConfigValue {
+    Text(String),
+    Number(i64)
+}
+
+/// Per-guild settings as stored in the `guild_config` table
+#[derive(Clone, Default, sqlx::FromRow)]
+pub struct GuildConfig {
+    pub prefix: Option<String>,
+    pub modlog_channel: Option<i64>,
+    pub mute_role: Option<i64>,
+    pub default_timeout: Option<i64>,
+    pub default_purge: Option<i64>,
+    pub snapshot_interval: Option<i64>
+}
+
+impl GuildConfig {
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(DEFAULT_PREFIX)
+    }
+
+    pub fn modlog_channel(&self) -> Option<serenity::ChannelId> {
+        self.modlog_channel.map(|channel| serenity::ChannelId::new(channel as u64))
+    }
+
+    pub fn mute_role(&self) -> Option<serenity::RoleId> {
+        self.mute_role.map(|role| serenity::RoleId::new(role as u64))
+    }
+
+    pub fn default_timeout(&self) -> Duration {
+        self.default_timeout
+            .map(|secs| Duration::from_secs(secs as u64))
+            .unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    pub fn default_purge(&self) -> u8 {
+        self.default_purge
+            .map(|amount| amount as u8)
+            .unwrap_or(DEFAULT_PURGE)
+    }
+
+    /// How often permission snapshots are taken automatically, `None` if they're disabled
+    pub fn snapshot_interval(&self) -> Option<Duration> {
+        self.snapshot_interval.map(|secs| Duration::from_secs(secs as u64))
+    }
+}
+
+/// In-memory cache of guild configs, filled lazily from the database
+#[derive(Default)]
+pub struct ConfigCache {
+    guilds: RwLock<HashMap<serenity::GuildId, GuildConfig>>
+}
+
+impl ConfigCache {
+    /// Get the config of a guild, loading it from the database if it isn't cached yet
+    pub async fn get(&self, db: &PgPool, guild_id: serenity::GuildId) -> Result<GuildConfig, sqlx::Error> {
+        let cached = self.guilds.read().unwrap().get(&guild_id).cloned();
+        if let Some(config) = cached {
+            return Ok(config);
+        }
+        let config: GuildConfig = sqlx::query_as(
+            "SELECT prefix, modlog_channel, mute_role, default_timeout, default_purge, snapshot_interval
+            FROM guild_config WHERE guild_id = $1"
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_optional(db)
+        .await?
+        .unwrap_or_default();
+        self.guilds.write().unwrap().insert(guild_id, config.clone());
+        Ok(config)
+    }
+
+    /// Store a setting for a guild
+    pub async fn set(
+        &self,
+        db: &PgPool,
+        guild_id: serenity::GuildId,
+        key: ConfigKey,
+        value: ConfigValue
+    ) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "INSERT INTO guild_config (guild_id, {column}) VALUES ($1, $2)
+            ON CONFLICT (guild_id) DO UPDATE SET {column} = EXCLUDED.{column}",
+            column = key.column()
+        );
+        let query = sqlx::query(&query).bind(guild_id.get() as i64);
+        match value {
+            ConfigValue::Text(text) => query.bind(text).execute(db).await?,
+            ConfigValue::Number(number) => query.bind(number).execute(db).await?
+        };
+        self.guilds.write().unwrap().remove(&guild_id);
+        Ok(())
+    }
+
+    /// Reset a setting of a guild to its default
+    pub async fn reset(&self, db: &PgPool, guild_id: serenity::GuildId, key: ConfigKey) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!("UPDATE guild_config SET {} = NULL WHERE guild_id = $1", key.column()))
+            .bind(guild_id.get() as i64)
+            .execute(db)
+            .await?;
+        self.guilds.write().unwrap().remove(&guild_id);
+        Ok(())
+    }
+}
+
+/// Resolve the prefix for an incoming message - the guild's configured prefix, or the default in DMs
+pub async fn dynamic_prefix(ctx: poise::PartialContext<'_, Data, Error>) -> Result<Option<String>, Error> {
+    let Some(guild_id) = ctx.guild_id else {
+        return Ok(Some(DEFAULT_PREFIX.to_string()));
+    };
+    let config = ctx.data.config.get(&ctx.data.db, guild_id).await?;
+    Ok(Some(config.prefix().to_string()))
+}
@@ -0,0 +1,12 @@
+use sqlx::PgPool;
+
+use crate::Error;
+
+/// Apply the schema migrations in `migrations/` that haven't been run against the database yet
+pub async fn migrate(pool: &PgPool) -> Result<(), Error> {
+    sqlx::migrate!()
+        .run(pool)
+        .await
+        .map_err(|e| format!("Failed to apply database migrations: {}", e))?;
+    Ok(())
+}
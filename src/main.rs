@@ -6,13 +6,32 @@ use shuttle_serenity::ShuttleSerenity;
 use sqlx;
 
 pub mod commands;
+pub mod config;
+pub mod db;
+pub mod scheduler;
 pub struct Data {
     pub start_time: std::time::SystemTime,
-    pub db: sqlx::PgPool
+    pub db: sqlx::PgPool,
+    pub config: config::ConfigCache
 } // User data, which is stored and accessible in all command invocations
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
+/// Shut the bot down if setup fails instead of leaving commands waiting on data that never arrives
+async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    match error {
+        poise::FrameworkError::Setup { error, framework, .. } => {
+            tracing::error!("Setup failed, shutting down: {}", error);
+            framework.shard_manager().shutdown_all().await;
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                tracing::error!("Error while handling error: {}", e);
+            }
+        }
+    }
+}
+
 
 #[shuttle_runtime::main]
 async fn main(
@@ -30,30 +49,57 @@ async fn main(
                 commands::misc::ping(),
                 commands::misc::help(),
                 commands::misc::dm(),
+                commands::config::config(),
                 commands::moderation::user::timeout(),
                 commands::moderation::user::untimeout(),
                 commands::moderation::user::ban(),
+                commands::moderation::user::tempban(),
+                commands::moderation::user::softban(),
                 commands::moderation::user::unban(),
                 commands::moderation::user::kick(),
+                commands::moderation::mass::massban(),
+                commands::moderation::mass::masskick(),
+                commands::moderation::user::warn(),
+                commands::moderation::user::warnings(),
+                commands::moderation::user::delwarn(),
+                commands::moderation::user::clearwarns(),
+                commands::moderation::user::warnpolicy(),
+                commands::moderation::mute::mute(),
+                commands::moderation::mute::unmute(),
+                commands::moderation::case::case(),
+                commands::moderation::case::cases(),
                 commands::moderation::role::role(),
                 commands::moderation::channel::lock(),
                 commands::moderation::channel::unlock(),
                 commands::moderation::channel::viewlock(),
                 commands::moderation::channel::unviewlock(),
+                commands::moderation::channel::overwrite(),
+                commands::moderation::lockdown::lockdown(),
+                commands::moderation::perms::perms(),
                 commands::moderation::purge::purge(),
+                commands::moderation::voice::voice(),
+                commands::moderation::thread::thread(),
                 commands::moderation::channel::slowmode()
             ],
-            prefix_options: poise::PrefixFrameworkOptions { prefix: Some("j.".into()), ..Default::default()},
+            prefix_options: poise::PrefixFrameworkOptions {
+                dynamic_prefix: Some(|ctx| Box::pin(config::dynamic_prefix(ctx))),
+                mention_as_prefix: true,
+                ..Default::default()
+            },
+            on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(
                 async move {
+                    db::migrate(&pool).await?;
+                    tokio::spawn(scheduler::run(ctx.http.clone(), pool.clone()));
                     poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                     Ok(
                         Data {
                             start_time: SystemTime::now(),
-                            db: pool
+                            db: pool,
+                            config: config::ConfigCache::default()
                         })
                     })
                 })
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::Duration;
+use poise::serenity_prelude as serenity;
+use sqlx::PgPool;
+
+use crate::commands::moderation::channel::TimedLock;
+use crate::commands::moderation::perms::{prune_snapshots, take_snapshot};
+use crate::Error;
+
+/// How often to check for expired moderation actions
+const TICK: Duration = Duration::from_secs(30);
+
+/// Discord error codes for an unknown channel, guild, member, overwrite, role, user or ban
+const UNKNOWN_RESOURCE: [isize; 7] = [10003, 10004, 10007, 10009, 10011, 10013, 10026];
+
+/// Whether a failed request will never succeed on retry, e.g. the user was already unbanned or left the guild -
+/// anything else, like missing permissions or a rate limit, is retried on the next tick
+fn is_final(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)) => {
+            response.status_code.as_u16() == 404 || UNKNOWN_RESOURCE.contains(&response.error.code)
+        }
+        _ => false
+    }
+}
+
+/// Lift every temporary ban that has expired
+async fn expire_tempbans(http: &serenity::Http, db: &PgPool, now: i64) -> Result<(), Error> {
+    let due: Vec<(i64, i64)> = sqlx::query_as("SELECT guild_id, user_id FROM tempbans WHERE expires_at <= $1")
+        .bind(now)
+        .fetch_all(db)
+        .await?;
+    for (guild_id, user_id) in due {
+        let guild = serenity::GuildId::new(guild_id as u64);
+        let user = serenity::UserId::new(user_id as u64);
+        if let Err(e) = http.remove_ban(guild, user, Some("Temporary ban expired")).await {
+            if !is_final(&e) {
+                tracing::error!("Failed to lift tempban of {} in {}: {}", user, guild, e);
+                continue;
+            }
+        }
+        sqlx::query("DELETE FROM tempbans WHERE guild_id = $1 AND user_id = $2 AND expires_at <= $3")
+            .bind(guild_id)
+            .bind(user_id)
+            .bind(now)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Lift every timed mute that has expired
+async fn expire_mutes(http: &serenity::Http, db: &PgPool, now: i64) -> Result<(), Error> {
+    let due: Vec<(i64, i64, i64)> = sqlx::query_as("SELECT guild_id, user_id, role_id FROM mutes WHERE expires_at <= $1")
+        .bind(now)
+        .fetch_all(db)
+        .await?;
+    for (guild_id, user_id, role_id) in due {
+        let guild = serenity::GuildId::new(guild_id as u64);
+        let user = serenity::UserId::new(user_id as u64);
+        let role = serenity::RoleId::new(role_id as u64);
+        if let Err(e) = http.remove_member_role(guild, user, role, Some("Mute expired")).await {
+            if !is_final(&e) {
+                tracing::error!("Failed to lift mute of {} in {}: {}", user, guild, e);
+                continue;
+            }
+        }
+        sqlx::query("DELETE FROM mutes WHERE guild_id = $1 AND user_id = $2 AND expires_at <= $3")
+            .bind(guild_id)
+            .bind(user_id)
+            .bind(now)
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Lift every timed lock and viewlock that has expired
+async fn expire_locks(http: &serenity::Http, db: &PgPool, now: i64) -> Result<(), Error> {
+    let due: Vec<TimedLock> = sqlx::query_as(
+        "SELECT guild_id, channel_id, target_type, target_id, perms, allow, deny FROM timed_locks WHERE expires_at <= $1"
+    )
+    .bind(now)
+    .fetch_all(db)
+    .await?;
+    for lock in due {
+        let channel = serenity::ChannelId::new(lock.channel_id as u64);
+        let result = match http.get_channel(channel).await {
+            Ok(serenity::Channel::Guild(channel)) => lock.lift(http, &channel).await,
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)
+        };
+        if let Err(e) = result {
+            if !is_final(&e) {
+                tracing::error!("Failed to lift timed lock of {} in {}: {}", lock.target_id, channel, e);
+                continue;
+            }
+        }
+        sqlx::query(
+            "DELETE FROM timed_locks
+            WHERE guild_id = $1 AND channel_id = $2 AND target_type = $3 AND target_id = $4 AND perms = $5 AND expires_at <= $6"
+        )
+        .bind(lock.guild_id)
+        .bind(lock.channel_id)
+        .bind(&lock.target_type)
+        .bind(lock.target_id)
+        .bind(lock.perms)
+        .bind(now)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Take a permission snapshot of every guild whose snapshot interval has passed since its last scheduled one
+async fn take_scheduled_snapshots(http: &serenity::Http, db: &PgPool, now: i64) -> Result<(), Error> {
+    let due: Vec<i64> = sqlx::query_scalar(
+        "SELECT guild_id FROM guild_config config WHERE snapshot_interval IS NOT NULL AND snapshot_interval + COALESCE(
+            (SELECT MAX(created_at) FROM permission_snapshots WHERE guild_id = config.guild_id AND created_by IS NULL), 0
+        ) <= $1"
+    )
+    .bind(now)
+    .fetch_all(db)
+    .await?;
+    for guild_id in due {
+        let guild = serenity::GuildId::new(guild_id as u64);
+        let channels = match http.get_channels(guild).await {
+            Ok(channels) => channels,
+            Err(e) if is_final(&e) => {
+                // The bot has left the guild, stop trying to snapshot it
+                tracing::warn!("Disabling scheduled permission snapshots of {}: {}", guild, e);
+                sqlx::query("UPDATE guild_config SET snapshot_interval = NULL WHERE guild_id = $1")
+                    .bind(guild_id)
+                    .execute(db)
+                    .await?;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Failed to fetch channels of {} for a permission snapshot: {}", guild, e);
+                continue;
+            }
+        };
+        if let Err(e) = take_snapshot(db, guild, &channels, None).await {
+            tracing::error!("Failed to take a scheduled permission snapshot of {}: {}", guild, e);
+            continue;
+        }
+        if let Err(e) = prune_snapshots(db, guild).await {
+            tracing::error!("Failed to prune permission snapshots of {}: {}", guild, e);
+        }
+    }
+    Ok(())
+}
+
+/// Run expired moderation actions and scheduled snapshots forever - the first pass runs immediately so anything
+/// that came due while the bot was offline is handled on startup
+pub async fn run(http: Arc<serenity::Http>, db: PgPool) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        let now = serenity::Timestamp::now().unix_timestamp();
+        if let Err(e) = expire_tempbans(&http, &db, now).await {
+            tracing::error!("Failed to expire tempbans: {}", e);
+        }
+        if let Err(e) = expire_mutes(&http, &db, now).await {
+            tracing::error!("Failed to expire mutes: {}", e);
+        }
+        if let Err(e) = expire_locks(&http, &db, now).await {
+            tracing::error!("Failed to expire timed locks: {}", e);
+        }
+        if let Err(e) = take_scheduled_snapshots(&http, &db, now).await {
+            tracing::error!("Failed to take scheduled permission snapshots: {}", e);
+        }
+    }
+}